@@ -0,0 +1,126 @@
+use enum_map::Enum;
+use glam::DVec3;
+
+use crate::game::*;
+use crate::graphics::object_bounds;
+use crate::orbits::*;
+use crate::targeting::*;
+
+// response rates of the attitude and velocity controllers, in 1/s
+pub const ATTITUDE_GAIN: f64 = 2.0;
+pub const ANGULAR_RATE_GAIN: f64 = 4.0;
+pub const VELOCITY_GAIN: f64 = 2.0;
+pub const MAX_TURN_RATE: f64 = 1.5;
+
+// approach stops this far from the target's bounding sphere, braking with a fraction of the available thrust
+pub const APPROACH_DISTANCE: f64 = 100.0;
+pub const APPROACH_MAX_SPEED: f64 = 300.0;
+pub const APPROACH_BRAKING: f64 = 0.5;
+
+#[derive(Enum, Clone, Copy, PartialEq)]
+pub enum Autopilot {
+    Off,
+    KillRotation,
+    Prograde,
+    Retrograde,
+    PointAtTarget,
+    MatchVelocity,
+    Approach,
+}
+
+// position, velocity and bounding radius of whatever the autopilot is flying relative to
+#[derive(Clone, Copy)]
+pub struct Reference {
+    pub position: DVec3,
+    pub velocity: DVec3,
+    pub radius: f64,
+}
+
+impl Autopilot {
+    pub fn name(self) -> &'static str {
+        match self {
+            Autopilot::Off => "OFF",
+            Autopilot::KillRotation => "KILL ROT",
+            Autopilot::Prograde => "PROGRADE",
+            Autopilot::Retrograde => "RETROGRADE",
+            Autopilot::PointAtTarget => "TARGET",
+            Autopilot::MatchVelocity => "MATCH VEL",
+            Autopilot::Approach => "APPROACH",
+        }
+    }
+
+    pub fn needs_target(self) -> bool {
+        matches!(self, Autopilot::PointAtTarget | Autopilot::MatchVelocity | Autopilot::Approach)
+    }
+}
+
+pub fn target_reference(asteroid: &Asteroid, planet: &Planet) -> Reference {
+    Reference {
+        position: asteroid.object.model.transform_point3(DVec3::ZERO),
+        velocity: asteroid_velocity(asteroid, planet),
+        radius: object_bounds(&asteroid.object).1,
+    }
+}
+
+// overrides the thrust channels the current mode needs, the rest are left to the player
+pub fn update_autopilot(ship: &mut Ship, target: Option<Reference>) {
+    if ship.jumping || ship.destroyed || ship.charging_jump {
+        return;
+    }
+    if ship.autopilot.needs_target() && target.is_none() {
+        ship.autopilot = Autopilot::Off;
+    }
+
+    match (ship.autopilot, target) {
+        (Autopilot::KillRotation, _) => hold_angular_velocity(ship, DVec3::ZERO),
+        (Autopilot::Prograde, _) => point_towards(ship, ship.velocity),
+        (Autopilot::Retrograde, _) => point_towards(ship, -ship.velocity),
+        (Autopilot::PointAtTarget, Some(target)) => point_towards(ship, target.position - ship.position),
+        (Autopilot::MatchVelocity, Some(target)) => match_velocity(ship, target.velocity),
+        (Autopilot::Approach, Some(target)) => {
+            let offset = target.position - ship.position;
+            let distance = offset.length() - target.radius - APPROACH_DISTANCE;
+            let braking = ship.stats.thrust * APPROACH_BRAKING;
+            let speed = f64::min(f64::sqrt(2.0 * braking * distance.abs()), APPROACH_MAX_SPEED).copysign(distance);
+            point_towards(ship, offset);
+            match_velocity(ship, target.velocity + offset.normalize_or_zero() * speed);
+        }
+        _ => {}
+    }
+}
+
+// turns the nose (-z) towards a world space direction while damping roll
+pub fn point_towards(ship: &mut Ship, direction: DVec3) {
+    let Some(direction) = (ship.rotation.inverse() * direction).try_normalize() else {
+        hold_angular_velocity(ship, DVec3::ZERO);
+        return;
+    };
+    let forward = DVec3::new(0.0, 0.0, -1.0);
+    let angle = forward.angle_between(direction);
+    let axis = forward.cross(direction).try_normalize().unwrap_or(DVec3::X);
+    let rate = f64::min(angle * ATTITUDE_GAIN, MAX_TURN_RATE);
+    hold_angular_velocity(ship, axis * rate);
+}
+
+// body space, same channel mapping as the brake
+pub fn hold_angular_velocity(ship: &mut Ship, angular_velocity: DVec3) {
+    let torque = ship.stats.inertia * (angular_velocity - ship.angular_velocity) * ANGULAR_RATE_GAIN;
+    let max = ship.stats.angular_thrust;
+    set_thrust_axis(ship, Thrust::PitchUp, Thrust::PitchDown, torque.x, max, max);
+    set_thrust_axis(ship, Thrust::YawLeft, Thrust::YawRight, torque.y, max, max);
+    set_thrust_axis(ship, Thrust::RollCCW, Thrust::RollCW, torque.z, max, max);
+}
+
+// world space velocity
+pub fn match_velocity(ship: &mut Ship, velocity: DVec3) {
+    let acceleration = ship.rotation.inverse() * (velocity - ship.velocity) * VELOCITY_GAIN;
+    let max = ship.stats.thrust;
+    set_thrust_axis(ship, Thrust::Right, Thrust::Left, acceleration.x, max, max);
+    set_thrust_axis(ship, Thrust::Up, Thrust::Down, acceleration.y, max, max);
+    set_thrust_axis(ship, Thrust::Back, Thrust::Front, acceleration.z, max, 2.0 * max);
+}
+
+fn set_thrust_axis(ship: &mut Ship, positive: Thrust, negative: Thrust, value: f64, max_positive: f64, max_negative: f64) {
+    ship.thrust[positive] = f64::clamp(value, 0.0, max_positive);
+    ship.thrust[negative] = f64::clamp(-value, 0.0, max_negative);
+}
@@ -0,0 +1,171 @@
+use std::f64::consts::FRAC_PI_2;
+
+use enum_map::enum_map;
+use glam::{DQuat, DVec3, EulerRot};
+use winit_input_helper::WinitInputHelper;
+
+use crate::game::*;
+use crate::mouse::*;
+
+// ship space, slightly above the center and ahead of it. the hull isn't drawn in the cockpit
+pub const COCKPIT_OFFSET: DVec3 = DVec3::new(0.0, 0.5, -1.0);
+
+// ship space, the damping is the fraction of the old position kept every 1/TICK_RATE seconds, so the
+// camera trails the same at any tick rate
+pub const CHASE_OFFSET: DVec3 = DVec3::new(0.0, 4.0, 10.0);
+pub const CHASE_DAMPING: f64 = 0.85;
+
+pub const ORBIT_DISTANCE: f64 = 15.0;
+pub const ORBIT_MIN_DISTANCE: f64 = 5.0;
+pub const ORBIT_MAX_DISTANCE: f64 = 500.0;
+// radians per pixel of mouse motion and the zoom factor per scroll line
+pub const ORBIT_SENSITIVITY: f64 = 0.005;
+pub const ORBIT_ZOOM: f64 = 0.1;
+
+pub const FREE_CAMERA_SPEED: f64 = 20.0;
+pub const FREE_CAMERA_TURN_RATE: f64 = 1.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CameraMode {
+    Cockpit,
+    Chase,
+    Orbit,
+    Free,
+}
+
+// orbit angles are in world space so the view stays steady while the ship tumbles. the free camera's move
+// and turn are -1..1 per axis in camera space, taken from the flight controls
+pub struct CameraRig {
+    pub mode: CameraMode,
+    pub chase_offset: DVec3,
+    pub chase_damping: f64,
+    pub orbit_yaw: f64,
+    pub orbit_pitch: f64,
+    pub orbit_distance: f64,
+    pub free_move: DVec3,
+    pub free_turn: DVec3,
+}
+
+impl Default for CameraRig {
+    fn default() -> Self {
+        CameraRig {
+            mode: CameraMode::Chase,
+            chase_offset: CHASE_OFFSET,
+            chase_damping: CHASE_DAMPING,
+            orbit_yaw: 0.0,
+            orbit_pitch: -0.3,
+            orbit_distance: ORBIT_DISTANCE,
+            free_move: DVec3::ZERO,
+            free_turn: DVec3::ZERO,
+        }
+    }
+}
+
+impl CameraMode {
+    pub fn next(self) -> CameraMode {
+        match self {
+            CameraMode::Cockpit => CameraMode::Chase,
+            CameraMode::Chase => CameraMode::Orbit,
+            CameraMode::Orbit => CameraMode::Free,
+            CameraMode::Free => CameraMode::Cockpit,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CameraMode::Cockpit => "COCKPIT",
+            CameraMode::Chase => "CHASE",
+            CameraMode::Orbit => "ORBIT",
+            CameraMode::Free => "FREE CAM",
+        }
+    }
+}
+
+pub fn update_camera_position(camera: &mut Camera, rig: &CameraRig, ship: &Ship, dt: f64) {
+    let up = ship.rotation * DVec3::Y;
+    match rig.mode {
+        CameraMode::Cockpit => {
+            set_camera_pose(camera, ship.position + ship.rotation * COCKPIT_OFFSET, ship.rotation);
+        }
+        CameraMode::Chase => {
+            let damping = rig.chase_damping.powf(dt * TICK_RATE);
+            let position = camera.position * damping + (ship.position + ship.rotation * rig.chase_offset) * (1.0 - damping);
+            let rotation = DQuat::look_at_rh(position, ship.position, up).inverse();
+            set_camera_pose(camera, position, rotation);
+        }
+        CameraMode::Orbit => {
+            let rotation = DQuat::from_euler(EulerRot::YXZ, rig.orbit_yaw, rig.orbit_pitch, 0.0);
+            set_camera_pose(camera, ship.position + rotation * DVec3::new(0.0, 0.0, rig.orbit_distance), rotation);
+        }
+        CameraMode::Free => {
+            let position = camera.position + camera.rotation * rig.free_move * FREE_CAMERA_SPEED * dt;
+            let rotation = (camera.rotation * DQuat::from_scaled_axis(rig.free_turn * FREE_CAMERA_TURN_RATE * dt)).normalize();
+            set_camera_pose(camera, position, rotation);
+        }
+    }
+}
+
+// dragging with the left or right mouse button turns the orbit camera, scrolling zooms. while the mouse
+// flies the ship the same motion would also steer it, so dragging is ignored then
+pub fn update_orbit_input(rig: &mut CameraRig, input: &WinitInputHelper, mouse: &MouseFlight) {
+    if mouse.mode == MouseMode::Off && (input.mouse_held(0) || input.mouse_held(1)) {
+        let (dx, dy) = input.mouse_diff();
+        rig.orbit_yaw -= dx as f64 * ORBIT_SENSITIVITY;
+        rig.orbit_pitch = f64::clamp(rig.orbit_pitch - dy as f64 * ORBIT_SENSITIVITY, -FRAC_PI_2 + 0.01, FRAC_PI_2 - 0.01);
+    }
+    let (_, scroll) = input.scroll_diff();
+    rig.orbit_distance = f64::clamp(rig.orbit_distance * (1.0 - scroll as f64 * ORBIT_ZOOM), ORBIT_MIN_DISTANCE, ORBIT_MAX_DISTANCE);
+}
+
+// photo mode, the thrust channels steer the camera in the same directions they would push the ship,
+// and the ship itself gets no flight controls and drifts. targeting and navigation commands still apply
+pub fn take_free_camera_input(rig: &mut CameraRig, input: &mut PlayerInput, stats: &ShipStats) {
+    let t = &input.thrust;
+    rig.free_move = DVec3::new(
+        t[Thrust::Right] - t[Thrust::Left],
+        t[Thrust::Up] - t[Thrust::Down],
+        t[Thrust::Back] - t[Thrust::Front] / 2.0,
+    ) / stats.thrust;
+    rig.free_turn = DVec3::new(
+        t[Thrust::PitchUp] - t[Thrust::PitchDown],
+        t[Thrust::YawLeft] - t[Thrust::YawRight],
+        t[Thrust::RollCCW] - t[Thrust::RollCW],
+    ) / stats.angular_thrust;
+    *input = PlayerInput {
+        thrust: enum_map! {_ => 0.0},
+        brake: false,
+        boost: false,
+        jump: false,
+        firing: false,
+        ..*input
+    };
+}
+
+// "x,y,z" as given on the command line
+pub fn parse_offset(text: &str) -> Option<DVec3> {
+    let values: Vec<f64> = text.split(',').map(|v| v.trim().parse().ok()).collect::<Option<_>>()?;
+    match values[..] {
+        [x, y, z] => Some(DVec3::new(x, y, z)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // one second of catching up to the ship ends in the same place at any tick rate
+    #[test]
+    fn chase_is_rate_independent() {
+        let positions = [30.0, 60.0, 240.0].map(|rate| {
+            let mut game = Game::with_seed(1);
+            game.camera.position = game.ship.position;
+            for _ in 0..rate as usize {
+                update_camera_position(&mut game.camera, &game.camera_rig, &game.ship, 1.0 / rate);
+            }
+            game.camera.position
+        });
+        assert!(positions[0].distance(positions[1]) < 1e-9);
+        assert!(positions[2].distance(positions[1]) < 1e-9);
+    }
+}
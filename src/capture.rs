@@ -0,0 +1,98 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use crate::graphics::RenderTarget;
+
+pub const GIF_DURATION: f64 = 5.0;
+pub const GIF_FPS: f64 = 25.0;
+pub const GIF_SCALE: u32 = 2;
+
+pub struct GifRecorder {
+    pub width: u32,
+    pub height: u32,
+    pub scale: u32,
+    // at the target's resolution, they are only upscaled while encoding to keep the recording small in memory
+    pub frames: Vec<Vec<u8>>,
+    pub remaining: f64,
+    pub accumulator: f64,
+}
+
+impl GifRecorder {
+    pub fn new(target: &RenderTarget, duration: f64, scale: u32) -> Self {
+        Self {
+            width: target.width,
+            height: target.height,
+            scale,
+            frames: Vec::with_capacity((duration * GIF_FPS) as usize + 1),
+            remaining: duration,
+            accumulator: 1.0 / GIF_FPS,
+        }
+    }
+
+    // grabs a frame every 1/GIF_FPS seconds, returns true once the duration is used up
+    pub fn capture(&mut self, target: &RenderTarget, dt: f64) -> bool {
+        self.accumulator += dt;
+        if self.accumulator >= 1.0 / GIF_FPS {
+            self.accumulator -= 1.0 / GIF_FPS;
+            self.frames.push(opaque(&target.frame));
+        }
+        self.remaining -= dt;
+        self.remaining <= 0.0
+    }
+
+    pub fn save(self, path: &Path) -> Result<(), gif::EncodingError> {
+        let (width, height) = ((self.width * self.scale) as u16, (self.height * self.scale) as u16);
+        let file = File::create(path)?;
+        let mut encoder = gif::Encoder::new(BufWriter::new(file), width, height, &[])?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        for rgba in &self.frames {
+            let mut rgba = upscale(rgba, self.width, self.height, self.scale);
+            let mut frame = gif::Frame::from_rgba_speed(width, height, &mut rgba, 10);
+            frame.delay = (100.0 / GIF_FPS) as u16;
+            encoder.write_frame(&frame)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn save_png(target: &RenderTarget, path: &Path) -> Result<(), png::EncodingError> {
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), target.width, target.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&opaque(&target.frame))?;
+    Ok(())
+}
+
+// the hud draws some colors with zero alpha, which pixels ignores but image files would not
+pub fn opaque(frame: &[u8]) -> Vec<u8> {
+    let mut frame = frame.to_vec();
+    for pixel in frame.chunks_exact_mut(4) {
+        pixel[3] = 0xff;
+    }
+    frame
+}
+
+pub fn upscale(frame: &[u8], width: u32, height: u32, scale: u32) -> Vec<u8> {
+    let mut scaled = Vec::with_capacity(frame.len() * (scale * scale) as usize);
+    for row in frame.chunks_exact(width as usize * 4).take(height as usize) {
+        let mut scaled_row = Vec::with_capacity(row.len() * scale as usize);
+        for pixel in row.chunks_exact(4) {
+            for _ in 0..scale {
+                scaled_row.extend_from_slice(pixel);
+            }
+        }
+        for _ in 0..scale {
+            scaled.extend_from_slice(&scaled_row);
+        }
+    }
+    scaled
+}
+
+pub fn timestamped_path(prefix: &str, extension: &str) -> PathBuf {
+    let t = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+    PathBuf::from(format!("{prefix}_{t}.{extension}"))
+}
@@ -0,0 +1,352 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use fxhash::FxBuildHasher;
+use glam::{DMat4, DVec3, IVec3};
+
+use crate::game::{apply_damage, end_jump, Asteroid, Object, Ship};
+use crate::graphics::object_bounds;
+use crate::orbits::{orbit_velocity, Planet};
+
+pub const COLLISION_CELL_SIZE: f64 = 2000.0;
+
+// moving spheres are inserted with padding and refreshed in slices, every one at least once per refresh
+// interval, the padding has to cover the distance the fastest asteroid travels in that time
+pub const COLLISION_GRID_REFRESH: f64 = 0.25;
+pub const COLLISION_GRID_PADDING: f64 = 150.0;
+
+// uniform grid over bounding spheres, spheres larger than a cell are kept in a separate list
+pub struct SpatialHash {
+    pub cell_size: f64,
+    pub cells: HashMap<IVec3, Vec<usize>, FxBuildHasher>,
+    pub large: Vec<usize>,
+    pub ranges: Vec<Option<(IVec3, IVec3)>>,
+    pub cursor: usize,
+}
+
+pub struct Contact {
+    pub normal: DVec3,
+    pub depth: f64,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f64) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::default(),
+            large: Vec::new(),
+            ranges: Vec::new(),
+            cursor: 0,
+        }
+    }
+
+    // keeps the cell vectors around, the set of occupied cells barely changes between rebuilds
+    pub fn clear(&mut self) {
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+        self.large.clear();
+        self.ranges.clear();
+    }
+
+    pub fn cell(&self, p: DVec3) -> IVec3 {
+        (p / self.cell_size).floor().as_ivec3()
+    }
+
+    pub fn insert(&mut self, index: usize, center: DVec3, radius: f64) {
+        if index >= self.ranges.len() {
+            self.ranges.resize(index + 1, None);
+        }
+        if radius > self.cell_size {
+            self.large.push(index);
+            return;
+        }
+        let (min, max) = (self.cell(center - radius), self.cell(center + radius));
+        self.ranges[index] = Some((min, max));
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    self.cells.entry(IVec3::new(x, y, z)).or_default().push(index);
+                }
+            }
+        }
+    }
+
+    // only touches the cells when the covered range changed, which is rare for slowly moving spheres
+    pub fn update(&mut self, index: usize, center: DVec3, radius: f64) {
+        let Some(Some((min, max))) = self.ranges.get(index).copied() else {
+            return;
+        };
+        if (self.cell(center - radius), self.cell(center + radius)) == (min, max) {
+            return;
+        }
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    if let Entry::Occupied(mut cell) = self.cells.entry(IVec3::new(x, y, z)) {
+                        cell.get_mut().retain(|&i| i != index);
+                        if cell.get().is_empty() {
+                            cell.remove();
+                        }
+                    }
+                }
+            }
+        }
+        self.ranges[index] = None;
+        self.insert(index, center, radius);
+    }
+
+    pub fn query(&self, center: DVec3, radius: f64, candidates: &mut Vec<usize>) {
+        candidates.clear();
+        candidates.extend_from_slice(&self.large);
+        let (min, max) = (self.cell(center - radius), self.cell(center + radius));
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    if let Some(cell) = self.cells.get(&IVec3::new(x, y, z)) {
+                        candidates.extend_from_slice(cell);
+                    }
+                }
+            }
+        }
+        candidates.sort_unstable();
+        candidates.dedup();
+    }
+}
+
+// centered on the model origin so that spinning asteroids don't invalidate the grid
+pub fn collision_sphere(object: &Object) -> (DVec3, f64) {
+    let (center, radius) = object_bounds(object);
+    let origin = object.model.transform_point3(DVec3::ZERO);
+    (origin, radius + (center - origin).length())
+}
+
+pub fn update_collision_grid(grid: &mut SpatialHash, asteroids: &[Asteroid]) {
+    grid.clear();
+    for (i, asteroid) in asteroids.iter().enumerate() {
+        let (center, radius) = collision_sphere(&asteroid.object);
+        grid.insert(i, center, radius + COLLISION_GRID_PADDING);
+    }
+}
+
+pub fn refresh_collision_grid(grid: &mut SpatialHash, asteroids: &[Asteroid], dt: f64) {
+    let count = usize::min(asteroids.len(), (asteroids.len() as f64 * dt / COLLISION_GRID_REFRESH).ceil() as usize);
+    for _ in 0..count {
+        grid.cursor = (grid.cursor + 1) % asteroids.len();
+        let (center, radius) = collision_sphere(&asteroids[grid.cursor].object);
+        grid.update(grid.cursor, center, radius + COLLISION_GRID_PADDING);
+    }
+}
+
+// treats the mesh as its convex hull, concave parts are filled in but every vertex is on or inside it
+pub fn sphere_vs_mesh(center: DVec3, radius: f64, object: &Object) -> Option<Contact> {
+    let (bounds_center, bounds_radius) = object_bounds(object);
+    if (center - bounds_center).length() > radius + bounds_radius {
+        return None;
+    }
+    let scale = object.model.x_axis.truncate().length();
+    let local_center = object.model.inverse().transform_point3(center);
+    let local_radius = radius / scale;
+
+    if object.mesh.planes.is_empty() {
+        return None;
+    }
+    let (mut distance, mut normal) = (f64::NEG_INFINITY, DVec3::ZERO);
+    for plane in &object.mesh.planes {
+        let d = plane.truncate().dot(local_center) - plane.w;
+        if d > local_radius {
+            return None;
+        }
+        if d > distance {
+            (distance, normal) = (d, plane.truncate());
+        }
+    }
+    Some(Contact {
+        normal: object.model.transform_vector3(normal).normalize(),
+        depth: (local_radius - distance) * scale,
+    })
+}
+
+// clips the ray against every hull plane in local space, returns the distance to the entry point
+pub fn ray_vs_mesh(origin: DVec3, direction: DVec3, max_distance: f64, object: &Object) -> Option<f64> {
+    let (bounds_center, bounds_radius) = object_bounds(object);
+    let along = f64::clamp((bounds_center - origin).dot(direction), 0.0, max_distance);
+    if (origin + direction * along - bounds_center).length() > bounds_radius {
+        return None;
+    }
+    if object.mesh.planes.is_empty() {
+        return None;
+    }
+    let inverse = object.model.inverse();
+    let local_origin = inverse.transform_point3(origin);
+    let local_direction = inverse.transform_vector3(direction);
+
+    let (mut t_enter, mut t_exit) = (0.0, max_distance);
+    for plane in &object.mesh.planes {
+        let normal = plane.truncate();
+        let distance = normal.dot(local_origin) - plane.w;
+        let denom = normal.dot(local_direction);
+        if denom == 0.0 {
+            if distance > 0.0 {
+                return None;
+            }
+            continue;
+        }
+        let t = -distance / denom;
+        if denom < 0.0 {
+            t_enter = f64::max(t_enter, t);
+        } else {
+            t_exit = f64::min(t_exit, t);
+        }
+        if t_enter > t_exit {
+            return None;
+        }
+    }
+    Some(t_enter)
+}
+
+// nearest asteroid hit along a ray, as (index, distance)
+pub fn raycast_asteroids(origin: DVec3, direction: DVec3, max_distance: f64, asteroids: &[Asteroid], grid: &SpatialHash) -> Option<(usize, f64)> {
+    let mut candidates = Vec::new();
+    grid.query(origin + direction * max_distance / 2.0, max_distance / 2.0, &mut candidates);
+    let mut closest: Option<(usize, f64)> = None;
+    for i in candidates {
+        let Some(t) = ray_vs_mesh(origin, direction, max_distance, &asteroids[i].object) else {
+            continue;
+        };
+        if closest.is_some_and(|(_, d)| d <= t) {
+            continue;
+        }
+        closest = Some((i, t));
+    }
+    closest
+}
+
+pub const COLLISION_RESTITUTION: f64 = 0.4;
+pub const COLLISION_SAFE_SPEED: f64 = 5.0;
+pub const COLLISION_DAMAGE: f64 = 1.0;
+
+// pushes the ship out of everything it overlaps and bounces it off the contact normal. only the velocity
+// relative to the orbiting asteroid counts, touching a rock the ship has matched speed with is harmless
+pub fn resolve_ship_collisions(ship: &mut Ship, asteroids: &[Asteroid], planet: &Planet, grid: &SpatialHash) {
+    let radius = ship.stats.collision_radius;
+    let mut candidates = Vec::new();
+    grid.query(ship.position, radius, &mut candidates);
+    for i in candidates {
+        let Some(contact) = sphere_vs_mesh(ship.position, radius, &asteroids[i].object) else {
+            continue;
+        };
+        if ship.jumping {
+            ship.jumping = false;
+            end_jump(ship);
+        }
+        ship.position += contact.normal * contact.depth;
+        let velocity = asteroids[i].orbit.map_or(DVec3::ZERO, |orbit| orbit_velocity(&orbit, planet.mu()));
+        let impact_speed = -(ship.velocity - velocity).dot(contact.normal);
+        if impact_speed > 0.0 {
+            ship.velocity += contact.normal * impact_speed * (1.0 + COLLISION_RESTITUTION);
+            apply_damage(ship, f64::max(0.0, impact_speed - COLLISION_SAFE_SPEED) * COLLISION_DAMAGE, true);
+        }
+    }
+    ship.hull.model = DMat4::from_rotation_translation(ship.rotation, ship.position);
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::rc::Rc;
+
+    use glam::DQuat;
+
+    use crate::game::Game;
+    use crate::meshes::{parse_obj, Mesh, ASTEROID_OBJ};
+
+    use super::*;
+
+    fn asteroid_object() -> Object {
+        Object {
+            mesh: Rc::new(Mesh::new(parse_obj(ASTEROID_OBJ))),
+            model: DMat4::from_scale_rotation_translation(DVec3::splat(100.0), DQuat::from_rotation_y(0.7), DVec3::new(500.0, -200.0, 1000.0)),
+            color: 0xffffffff,
+            fill: 0x000000ff,
+            lods: &[],
+        }
+    }
+
+    // the local vertex lying furthest outside the planes of the mesh's own faces, and by how much
+    pub fn outlying_vertex(mesh: &Mesh) -> (DVec3, f64) {
+        let outside = |v: DVec3| mesh.polygons.iter().map(|p| {
+            let normal = (p[1] - p[0]).cross(p[2] - p[0]).normalize();
+            normal.dot(v - p[0])
+        }).fold(f64::NEG_INFINITY, f64::max);
+        mesh.polygons.iter().flatten().map(|v| (*v, outside(*v))).max_by(|a, b| a.1.total_cmp(&b.1)).unwrap()
+    }
+
+    // the first rock in orbit around the planet, in the seed the collision, weapon and mining tests share
+    pub fn orbiting_rock() -> (Game, usize) {
+        let game = Game::with_seed(1);
+        let i = (1..game.asteroids.len()).find(|&i| game.asteroids[i].orbit.is_some()).unwrap();
+        (game, i)
+    }
+
+    // the world position of the object's outlying vertex and the direction away from the object's center
+    pub fn outlying_point(object: &Object) -> (DVec3, DVec3) {
+        let (vertex, _) = outlying_vertex(&object.mesh);
+        (object.model.transform_point3(vertex), object.model.transform_vector3(vertex).normalize())
+    }
+
+    #[test]
+    fn sphere_touches_outlying_vertex() {
+        let object = asteroid_object();
+        let (vertex, outside) = outlying_vertex(&object.mesh);
+        assert!(outside > 0.1, "asteroid mesh is convex");
+        let outward = object.model.transform_vector3(vertex).normalize();
+        let center = object.model.transform_point3(vertex) + outward * 1.0;
+
+        let contact = sphere_vs_mesh(center, 2.0, &object).expect("no contact at the outlying vertex");
+        assert!(contact.normal.dot(outward) > 0.0);
+        assert!(contact.depth > 0.0);
+        assert!(sphere_vs_mesh(center + outward * 2.0, 2.0, &object).is_none());
+    }
+
+    // the ring moves at hundreds of m/s, a ship flying along with a rock only bumps it gently
+    #[test]
+    fn matched_speed_touch_is_harmless() {
+        let (mut game, i) = orbiting_rock();
+        let asteroid = &game.asteroids[i];
+        let velocity = orbit_velocity(&asteroid.orbit.unwrap(), game.planet.mu());
+        assert!(velocity.length() > 100.0);
+        let (point, outward) = outlying_point(&asteroid.object);
+        game.ship.position = point;
+        game.ship.velocity = velocity - outward;
+        let (integrity, shield) = (game.ship.integrity, game.ship.shield);
+
+        resolve_ship_collisions(&mut game.ship, &game.asteroids, &game.planet, &game.collision_grid);
+        assert_eq!((game.ship.integrity, game.ship.shield), (integrity, shield));
+        assert!((game.ship.velocity - velocity).dot(outward) > 0.0);
+    }
+
+    // lasers, projectiles and the mining beam all use this, a shot at the edge of a rock has to land
+    #[test]
+    fn ray_hits_outlying_vertex() {
+        let (game, i) = orbiting_rock();
+        let (point, outward) = outlying_point(&game.asteroids[i].object);
+        let origin = point + outward * 50.0;
+
+        let (hit, t) = raycast_asteroids(origin, -outward, 100.0, &game.asteroids, &game.collision_grid).expect("shot passed through");
+        assert_eq!(hit, i);
+        assert!(t <= 50.0 + 1e-6);
+    }
+
+    // points and lines have no hull to hit, the bounds check alone would report a hit at the origin
+    #[test]
+    fn ray_misses_mesh_without_planes() {
+        let point = Object {
+            mesh: Rc::new(Mesh::new(vec![vec![DVec3::ZERO]])),
+            model: DMat4::IDENTITY,
+            color: 0xffffffff,
+            fill: 0x000000ff,
+            lods: &[],
+        };
+        assert!(point.mesh.planes.is_empty());
+        assert!(ray_vs_mesh(DVec3::new(0.0, 0.0, -1.0), DVec3::Z, 2.0, &point).is_none());
+    }
+}
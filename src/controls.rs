@@ -0,0 +1,310 @@
+use enum_map::{enum_map, Enum, EnumMap};
+use glam::DVec3;
+use log::warn;
+use winit::keyboard::KeyCode;
+use winit_input_helper::WinitInputHelper;
+
+use crate::game::*;
+use crate::graphics::*;
+use crate::save::*;
+use crate::sprites::*;
+
+// file name on native, local storage key on wasm
+pub const CONTROLS_NAME: &str = "controls.cfg";
+
+// fixed so the screen can't be locked out by rebinding
+pub const REBIND_KEY: KeyCode = KeyCode::F1;
+pub const REBIND_ROWS: usize = 17;
+
+#[derive(Enum, Clone, Copy, PartialEq)]
+pub enum Action {
+    ThrustLeft,
+    ThrustRight,
+    ThrustUp,
+    ThrustDown,
+    ThrustFront,
+    ThrustBack,
+    YawLeft,
+    YawRight,
+    PitchUp,
+    PitchDown,
+    RollCCW,
+    RollCW,
+    Brake,
+    Boost,
+    Jump,
+    Fire,
+    NextWeapon,
+    CycleTarget,
+    ClearTarget,
+    KillRotation,
+    Prograde,
+    Retrograde,
+    PointAtTarget,
+    MatchVelocity,
+    Approach,
+    AutopilotOff,
+    CycleDestination,
+    Bookmark,
+    MouseFlight,
+    CameraMode,
+    Screenshot,
+    RecordGif,
+    Save,
+    Load,
+}
+
+pub type Bindings = EnumMap<Action, KeyCode>;
+
+// keys that can be bound and written to the config file, by their winit names
+pub const KEYS: &[KeyCode] = &[
+    KeyCode::KeyA, KeyCode::KeyB, KeyCode::KeyC, KeyCode::KeyD, KeyCode::KeyE, KeyCode::KeyF, KeyCode::KeyG,
+    KeyCode::KeyH, KeyCode::KeyI, KeyCode::KeyJ, KeyCode::KeyK, KeyCode::KeyL, KeyCode::KeyM, KeyCode::KeyN,
+    KeyCode::KeyO, KeyCode::KeyP, KeyCode::KeyQ, KeyCode::KeyR, KeyCode::KeyS, KeyCode::KeyT, KeyCode::KeyU,
+    KeyCode::KeyV, KeyCode::KeyW, KeyCode::KeyX, KeyCode::KeyY, KeyCode::KeyZ,
+    KeyCode::Digit0, KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4,
+    KeyCode::Digit5, KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+    KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6, KeyCode::F7,
+    KeyCode::F8, KeyCode::F9, KeyCode::F10, KeyCode::F11, KeyCode::F12,
+    KeyCode::Space, KeyCode::Tab, KeyCode::Enter, KeyCode::Backspace, KeyCode::CapsLock,
+    KeyCode::AltLeft, KeyCode::AltRight, KeyCode::ShiftLeft, KeyCode::ShiftRight, KeyCode::ControlLeft, KeyCode::ControlRight,
+    KeyCode::ArrowUp, KeyCode::ArrowDown, KeyCode::ArrowLeft, KeyCode::ArrowRight,
+    KeyCode::Insert, KeyCode::Delete, KeyCode::Home, KeyCode::End, KeyCode::PageUp, KeyCode::PageDown,
+    KeyCode::Comma, KeyCode::Period, KeyCode::Slash, KeyCode::Semicolon, KeyCode::Quote, KeyCode::Backquote,
+    KeyCode::BracketLeft, KeyCode::BracketRight, KeyCode::Backslash, KeyCode::Minus, KeyCode::Equal,
+    KeyCode::Numpad0, KeyCode::Numpad1, KeyCode::Numpad2, KeyCode::Numpad3, KeyCode::Numpad4,
+    KeyCode::Numpad5, KeyCode::Numpad6, KeyCode::Numpad7, KeyCode::Numpad8, KeyCode::Numpad9,
+    KeyCode::NumpadAdd, KeyCode::NumpadSubtract, KeyCode::NumpadMultiply, KeyCode::NumpadDivide,
+    KeyCode::NumpadDecimal, KeyCode::NumpadEnter,
+];
+
+// selection and whether the next key press gets bound to the selected action
+#[derive(Default)]
+pub struct RebindScreen {
+    pub open: bool,
+    pub selected: usize,
+    pub waiting: bool,
+}
+
+impl Action {
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::ThrustLeft => "THRUST LEFT",
+            Action::ThrustRight => "THRUST RIGHT",
+            Action::ThrustUp => "THRUST UP",
+            Action::ThrustDown => "THRUST DOWN",
+            Action::ThrustFront => "THRUST FRONT",
+            Action::ThrustBack => "THRUST BACK",
+            Action::YawLeft => "YAW LEFT",
+            Action::YawRight => "YAW RIGHT",
+            Action::PitchUp => "PITCH UP",
+            Action::PitchDown => "PITCH DOWN",
+            Action::RollCCW => "ROLL CCW",
+            Action::RollCW => "ROLL CW",
+            Action::Brake => "BRAKE",
+            Action::Boost => "BOOST",
+            Action::Jump => "JUMP",
+            Action::Fire => "FIRE",
+            Action::NextWeapon => "NEXT WEAPON",
+            Action::CycleTarget => "CYCLE TARGET",
+            Action::ClearTarget => "CLEAR TARGET",
+            Action::KillRotation => "AP KILL ROT",
+            Action::Prograde => "AP PROGRADE",
+            Action::Retrograde => "AP RETROGRADE",
+            Action::PointAtTarget => "AP TARGET",
+            Action::MatchVelocity => "AP MATCH VEL",
+            Action::Approach => "AP APPROACH",
+            Action::AutopilotOff => "AP OFF",
+            Action::CycleDestination => "NAV CYCLE",
+            Action::Bookmark => "BOOKMARK",
+            Action::MouseFlight => "MOUSE FLIGHT",
+            Action::CameraMode => "CAMERA MODE",
+            Action::Screenshot => "SCREENSHOT",
+            Action::RecordGif => "RECORD GIF",
+            Action::Save => "SAVE",
+            Action::Load => "LOAD",
+        }
+    }
+}
+
+pub fn thrust_action(thrust: Thrust) -> Action {
+    match thrust {
+        Thrust::Left => Action::ThrustLeft,
+        Thrust::Right => Action::ThrustRight,
+        Thrust::Up => Action::ThrustUp,
+        Thrust::Down => Action::ThrustDown,
+        Thrust::Front => Action::ThrustFront,
+        Thrust::Back => Action::ThrustBack,
+        Thrust::YawLeft => Action::YawLeft,
+        Thrust::YawRight => Action::YawRight,
+        Thrust::PitchUp => Action::PitchUp,
+        Thrust::PitchDown => Action::PitchDown,
+        Thrust::RollCCW => Action::RollCCW,
+        Thrust::RollCW => Action::RollCW,
+    }
+}
+
+pub fn default_bindings() -> Bindings {
+    enum_map! {
+        Action::ThrustLeft => KeyCode::KeyA,
+        Action::ThrustRight => KeyCode::KeyD,
+        Action::ThrustUp => KeyCode::KeyR,
+        Action::ThrustDown => KeyCode::KeyF,
+        Action::ThrustFront => KeyCode::KeyW,
+        Action::ThrustBack => KeyCode::KeyS,
+        Action::YawLeft => KeyCode::KeyJ,
+        Action::YawRight => KeyCode::KeyL,
+        Action::PitchUp => KeyCode::KeyK,
+        Action::PitchDown => KeyCode::KeyI,
+        Action::RollCCW => KeyCode::KeyU,
+        Action::RollCW => KeyCode::KeyO,
+        Action::Brake => KeyCode::Space,
+        Action::Boost => KeyCode::Tab,
+        Action::Jump => KeyCode::AltLeft,
+        Action::Fire => KeyCode::KeyE,
+        Action::NextWeapon => KeyCode::KeyQ,
+        Action::CycleTarget => KeyCode::KeyT,
+        Action::ClearTarget => KeyCode::KeyG,
+        Action::KillRotation => KeyCode::Digit1,
+        Action::Prograde => KeyCode::Digit2,
+        Action::Retrograde => KeyCode::Digit3,
+        Action::PointAtTarget => KeyCode::Digit4,
+        Action::MatchVelocity => KeyCode::Digit5,
+        Action::Approach => KeyCode::Digit6,
+        Action::AutopilotOff => KeyCode::Digit0,
+        Action::CycleDestination => KeyCode::KeyN,
+        Action::Bookmark => KeyCode::KeyB,
+        Action::MouseFlight => KeyCode::KeyM,
+        Action::CameraMode => KeyCode::KeyC,
+        Action::Screenshot => KeyCode::F2,
+        Action::RecordGif => KeyCode::F3,
+        Action::Save => KeyCode::F5,
+        Action::Load => KeyCode::F9,
+    }
+}
+
+pub fn key_name(key: KeyCode) -> String {
+    format!("{:?}", key)
+}
+
+// short uppercase label for the hud boxes and the rebinding screen
+pub fn key_label(key: KeyCode) -> String {
+    match key {
+        KeyCode::AltLeft | KeyCode::AltRight => "ALT".to_string(),
+        KeyCode::ShiftLeft | KeyCode::ShiftRight => "SHIFT".to_string(),
+        KeyCode::ControlLeft | KeyCode::ControlRight => "CTRL".to_string(),
+        _ => {
+            let name = key_name(key);
+            let name = name.strip_prefix("Key").or(name.strip_prefix("Digit")).or(name.strip_prefix("Arrow")).unwrap_or(&name);
+            name.replace("Numpad", "NUM").to_uppercase()
+        }
+    }
+}
+
+pub fn key_labels(bindings: &Bindings) -> EnumMap<Action, String> {
+    EnumMap::from_fn(|action| key_label(bindings[action]))
+}
+
+// cut to the number of characters that fit into a hud box
+pub fn fit_label(label: &str, chars: usize) -> &str {
+    &label[..usize::min(label.len(), chars)]
+}
+
+// one "action_name = KeyName" per line, missing or broken lines keep their defaults
+pub fn parse_bindings(text: &str) -> Bindings {
+    let mut bindings = default_bindings();
+    for (name, key) in config_entries(text, "controls") {
+        let action = (0..Action::LENGTH).map(Action::from_usize).find(|a| config_name(*a) == name);
+        let code = KEYS.iter().find(|k| key_name(**k) == key);
+        match (action, code) {
+            (Some(action), Some(&code)) => bindings[action] = code,
+            (None, _) => warn!("controls: unknown action \"{name}\""),
+            (_, None) => warn!("controls: unknown key \"{key}\""),
+        }
+    }
+    bindings
+}
+
+pub fn load_bindings() -> Bindings {
+    load_config(CONTROLS_NAME, "controls", parse_bindings, format_bindings, default_bindings())
+}
+
+pub fn format_bindings(bindings: &Bindings) -> String {
+    let mut text = String::from("# action = key, key names as in winit's KeyCode\n");
+    for (action, key) in bindings {
+        text += &format!("{} = {}\n", config_name(action), key_name(*key));
+    }
+    text
+}
+
+fn config_name(action: Action) -> String {
+    action.name().to_lowercase().replace(' ', "_")
+}
+
+// arrows move the selection, enter waits for the next key, delete restores the default and escape closes.
+// binding a key that is already in use swaps it with the selected action's old key, returns true on changes
+pub fn update_rebind_screen(screen: &mut RebindScreen, input: &WinitInputHelper, bindings: &mut Bindings) -> bool {
+    if screen.waiting {
+        if input.key_pressed(KeyCode::Escape) {
+            screen.waiting = false;
+        } else if let Some(&key) = KEYS.iter().find(|k| input.key_pressed(**k)) {
+            screen.waiting = false;
+            let action = Action::from_usize(screen.selected);
+            if let Some((other, _)) = bindings.iter().find(|(_, k)| **k == key) {
+                bindings[other] = bindings[action];
+            }
+            bindings[action] = key;
+            return true;
+        }
+        return false;
+    }
+
+    let count = Action::LENGTH;
+    if input.key_pressed(KeyCode::Escape) {
+        screen.open = false;
+    }
+    if input.key_pressed(KeyCode::ArrowUp) {
+        screen.selected = (screen.selected + count - 1) % count;
+    }
+    if input.key_pressed(KeyCode::ArrowDown) {
+        screen.selected = (screen.selected + 1) % count;
+    }
+    if input.key_pressed(KeyCode::ArrowLeft) || input.key_pressed(KeyCode::ArrowRight) {
+        screen.selected = usize::min((screen.selected + REBIND_ROWS) % (2 * REBIND_ROWS), count - 1);
+    }
+    if input.key_pressed(KeyCode::Enter) {
+        screen.waiting = true;
+    }
+    if input.key_pressed(KeyCode::Delete) || input.key_pressed(KeyCode::Backspace) {
+        let action = Action::from_usize(screen.selected);
+        let key = default_bindings()[action];
+        if let Some((other, _)) = bindings.iter().find(|(_, k)| **k == key) {
+            bindings[other] = bindings[action];
+        }
+        bindings[action] = key;
+        return true;
+    }
+    false
+}
+
+// two columns of actions over a blanked screen, the selected row is inverted
+pub fn draw_rebind_screen(target: &mut RenderTarget, screen: &RebindScreen, bindings: &Bindings) {
+    let (w, h) = (target.width as f64, target.height as f64);
+    draw_rectangle_fill(target, DVec3::new(0.0, 0.0, 0.0), DVec3::new(w, h, 0.0), 0x000000ff);
+    draw_text(target, DVec3::new(4.0, h - 10.0, 0.0), "CONTROLS", FONT_5PX, 6, 1, 0xffffffff);
+    let hint = if screen.waiting {"PRESS A KEY  ESC CANCEL"} else {"ENTER BIND  DEL RESET  ESC BACK"};
+    draw_text(target, DVec3::new(w - 4.0 - (hint.len() * 6) as f64, h - 10.0, 0.0), hint, FONT_5PX, 6, 1, 0xffffffff);
+
+    for (i, (action, key)) in bindings.iter().enumerate() {
+        let x = 4.0 + (i / REBIND_ROWS) as f64 * w / 2.0;
+        let y = h - 24.0 - (i % REBIND_ROWS) as f64 * 8.0;
+        let selected = i == screen.selected;
+        let key = if selected && screen.waiting {"...".to_string()} else {key_label(*key)};
+        if selected {
+            draw_rectangle_fill(target, DVec3::new(x - 1.0, y - 1.0, 0.0), DVec3::new(x + w / 2.0 - 8.0, y + 5.0, 0.0), 0xffffffff);
+        }
+        let color = if selected {0x000000ff} else {0xffffffff};
+        draw_text(target, DVec3::new(x, y, 0.0), action.name(), FONT_5PX, 6, 1, color);
+        draw_text(target, DVec3::new(x + w / 2.0 - 10.0 - (key.len() * 6) as f64, y, 0.0), &key, FONT_5PX, 6, 1, color);
+    }
+}
@@ -0,0 +1,91 @@
+use std::f64::consts::TAU;
+use std::rc::Rc;
+
+use glam::{DMat4, DVec3};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use rand_distr::StandardNormal;
+
+use crate::game::*;
+use crate::meshes::*;
+use crate::orbits::*;
+use crate::transform::*;
+
+// system positions are in light years, the home system sits at the center
+pub const SYSTEM_COUNT: usize = 12;
+pub const GALAXY_RADIUS: f64 = 50.0;
+
+// interstellar jumps spend a fixed time in the tunnel regardless of distance
+pub const SYSTEM_TRANSIT_TIME: f64 = 5.0;
+
+// the star is a disc on the sky just in front of the background stars
+pub const STAR_DISTANCE: f64 = FAR / 2.0 * 0.9;
+pub const STAR_RADIUS: f64 = 10000.0;
+
+pub const STAR_COLORS: &[u32] = &[0xff6040ff, 0xffa060ff, 0xffe0a0ff, 0xfffff0ff, 0xa0c0ffff];
+pub const NAME_SYLLABLES: &[&str] = &["KA", "TOR", "VEX", "LI", "ON", "ZU", "MAR", "ES", "DRA", "NI", "SOL", "QUA"];
+
+// everything needed to regenerate a system, its contents are rebuilt from the seed on every visit
+pub struct StarSystem {
+    pub name: String,
+    pub position: DVec3,
+    pub seed: u64,
+    pub star_color: u32,
+    pub planet_scale: f64,
+    pub ring: bool,
+    pub belt: bool,
+}
+
+pub fn generate_galaxy(rng: &mut impl Rng) -> Vec<StarSystem> {
+    let mut systems = Vec::with_capacity(SYSTEM_COUNT);
+    for i in 0..SYSTEM_COUNT {
+        let angle = rng.random::<f64>() * TAU;
+        let distance = if i == 0 {0.0} else {rng.random_range(GALAXY_RADIUS * 0.1..GALAXY_RADIUS)};
+        let name = (0..rng.random_range(2..=3)).map(|_| NAME_SYLLABLES[rng.random_range(0..NAME_SYLLABLES.len())]).collect();
+        systems.push(StarSystem {
+            name,
+            position: DVec3::new(angle.cos() * distance, rng.random_range(-1.0..1.0), angle.sin() * distance),
+            seed: rng.random(),
+            star_color: STAR_COLORS[rng.random_range(0..STAR_COLORS.len())],
+            planet_scale: rng.random_range(15000.0..45000.0),
+            ring: i == 0 || rng.random::<f64>() < 0.7,
+            belt: rng.random::<f64>() < 0.5,
+        });
+    }
+    systems
+}
+
+// background stars with the system's star among them, the planet and the asteroids. seeded with a fixed
+// algorithm so a system seed gives the same system across rand releases
+pub fn generate_system(system: &StarSystem) -> (Vec<Object>, Planet, Vec<Asteroid>) {
+    let mut rng = ChaCha12Rng::seed_from_u64(system.seed);
+    let mut stars = generate_stars(&mut rng);
+    stars.push(generate_star(system, &mut rng));
+    let (planet, asteroids) = generate_asteroids(system, &mut rng);
+    (stars, planet, asteroids)
+}
+
+// follows the camera like the background stars, so the mesh itself is placed at the star's distance
+pub fn generate_star(system: &StarSystem, rng: &mut impl Rng) -> Object {
+    let direction = DVec3::new(
+        rng.sample::<f64, StandardNormal>(StandardNormal),
+        rng.sample::<f64, StandardNormal>(StandardNormal),
+        rng.sample::<f64, StandardNormal>(StandardNormal),
+    ).normalize();
+    let model = DMat4::from_translation(direction * STAR_DISTANCE) * DMat4::from_scale(DVec3::ONE * STAR_RADIUS);
+    Object {
+        mesh: Rc::new(Mesh::new(transform_mesh(&parse_obj(PLANET_OBJ), model))),
+        model: DMat4::IDENTITY,
+        color: system.star_color,
+        fill: system.star_color,
+        lods: FULL_LODS,
+    }
+}
+
+pub fn system_distance(galaxy: &[StarSystem], from: usize, to: usize) -> f64 {
+    (galaxy[to].position - galaxy[from].position).length()
+}
+
+pub fn system_direction(galaxy: &[StarSystem], from: usize, to: usize) -> DVec3 {
+    (galaxy[to].position - galaxy[from].position).normalize_or_zero()
+}
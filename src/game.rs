@@ -3,22 +3,82 @@ use std::rc::Rc;
 
 use enum_map::{enum_map, Enum, EnumMap};
 use glam::DQuat;
-use glam::{DMat4, DVec3};
-use rand::Rng;
+use glam::{DMat3, DMat4, DVec3, DVec4};
+use rand_chacha::ChaCha12Rng;
+use rand::{Rng, SeedableRng};
 use rand_distr::StandardNormal;
 
-use crate::transform::FAR;
+use crate::transform::{frustum_planes, FAR};
 use crate::{graphics::*, HEIGHT, WIDTH};
 use crate::sprites::*;
 use crate::meshes::*;
+use crate::collision::*;
+use crate::weapons::*;
+use crate::mining::*;
+use crate::orbits::*;
+use crate::targeting::*;
+use crate::autopilot::*;
+use crate::navigation::*;
+use crate::galaxy::*;
+use crate::controls::*;
+use crate::replay::*;
+use crate::camera::*;
+
+pub const TICK_RATE: f64 = 60.0;
+// slower ticks make the integration coarse, faster ones cost more than a frame can spend
+pub const MIN_TICK_RATE: f64 = 10.0;
+pub const MAX_TICK_RATE: f64 = 1000.0;
+pub const MAX_FRAME_TIME: f64 = 0.25;
 
 pub struct Game {
+    pub seed: u64,
+    pub rng: ChaCha12Rng,
+    pub tick_rate: f64,
+    pub accumulator: f64,
+    pub previous_ship: Pose,
+    pub previous_camera: Pose,
     pub ship: Ship,
     pub camera: Camera,
+    pub camera_rig: CameraRig,
     pub stars: Vec<Object>,
     pub dust: Vec<Object>,
     pub particles: Vec<Particle>,
+    pub projectiles: Vec<Projectile>,
     pub asteroids: Vec<Asteroid>,
+    pub planet: Planet,
+    pub galaxy: Vec<StarSystem>,
+    pub system: usize,
+    // ticks the asteroids of the current system have moved since it was generated
+    pub system_ticks: u64,
+    pub transit: f64,
+    pub target_lock: Option<usize>,
+    pub destination: Option<Destination>,
+    pub bookmarks: Vec<Bookmark>,
+    pub collision_grid: SpatialHash,
+    pub render_stats: RenderStats,
+    pub key_labels: EnumMap<Action, String>,
+    pub input: PlayerInput,
+    pub recording: Option<Recording>,
+    pub replay: Option<Replay>,
+}
+
+// what the player asks of the ship, main fills it once per frame and every tick applies it. everything
+// that changes the simulation goes through here so recordings replay it. only thrust, brake and firing
+// are held, the rest are presses and only apply to the next tick
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct PlayerInput {
+    pub thrust: EnumMap<Thrust, f64>,
+    pub brake: bool,
+    pub boost: bool,
+    pub jump: bool,
+    pub firing: bool,
+    pub next_weapon: bool,
+    pub cycle_target: bool,
+    pub clear_target: bool,
+    // toggles the mode, Off switches the autopilot off
+    pub autopilot: Option<Autopilot>,
+    pub cycle_destination: bool,
+    pub bookmark: bool,
 }
 
 pub struct Ship {
@@ -26,15 +86,29 @@ pub struct Ship {
     pub velocity: DVec3,
     pub acceleration: DVec3,
     pub rotation: DQuat,
-    pub angular_velocity: DQuat,
-    pub angular_acceleration: DQuat,
+    pub angular_velocity: DVec3,
+    pub angular_acceleration: DVec3,
     pub thrust: EnumMap<Thrust, f64>,
     pub boost: f64,
     pub boost_cooldown: f64,
     pub jumping: bool,
     pub charging_jump: bool,
     pub jump_charge: f64,
+    // set by navigation every tick, the charge doesn't run down while the nose is off the destination
+    pub jump_aligned: bool,
     pub brake: bool,
+    pub autopilot: Autopilot,
+    pub integrity: f64,
+    pub shield: f64,
+    pub shield_delay: f64,
+    pub destroyed: bool,
+    pub respawn_timer: f64,
+    pub weapon: Weapon,
+    pub firing: bool,
+    pub weapon_cooldown: f64,
+    pub laser: Option<DVec3>,
+    pub cargo: EnumMap<Resource, f64>,
+    pub mining: Option<(Resource, f64)>,
     pub hull: Object,
     pub thrusters: EnumMap<Thrust, Object>,
     pub stats: ShipStats,
@@ -48,6 +122,26 @@ pub struct ShipStats {
     pub boost_cooldown: f64,
     pub jump_speed: f64,
     pub jump_charge: f64,
+    pub inertia: DMat3,
+    pub collision_radius: f64,
+    pub max_integrity: f64,
+    pub max_shield: f64,
+    pub shield_regen: f64,
+    pub shield_delay: f64,
+    pub integrity_regen: f64,
+    pub g_tolerance: f64,
+    pub g_damage: f64,
+    pub respawn_time: f64,
+    pub laser_range: f64,
+    pub laser_dps: f64,
+    pub cannon_speed: f64,
+    pub cannon_damage: f64,
+    pub cannon_rate: f64,
+    pub cannon_lifetime: f64,
+    pub mass: f64,
+    pub cargo_capacity: f64,
+    pub mining_range: f64,
+    pub mining_rate: f64,
 }
 
 #[derive(Enum)]
@@ -66,55 +160,166 @@ pub enum Thrust {
     RollCW,
 }
 
+#[derive(Clone, Copy)]
+pub struct Pose {
+    pub position: DVec3,
+    pub rotation: DQuat,
+}
+
+#[derive(Clone)]
 pub struct Camera {
     pub position: DVec3,
     pub rotation: DQuat,
     pub fov: f64,
+    pub aspect: f64,
     pub model: DMat4,
     pub view: DMat4,
+    pub frustum: [DVec4; 6],
 }
 
 #[derive(Clone)]
 pub struct Object {
-    pub mesh: Rc<Vec<Vec<DVec3>>>,
+    pub mesh: Rc<Mesh>,
     pub model: DMat4,
     pub color: u32,
     pub fill: u32,
+    pub lods: &'static [LodLevel],
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Lod {
+    Mesh,
+    Wireframe,
+    Point,
+    Skip,
+}
+
+// the first level whose min_size (projected diameter in pixels) is reached gets drawn
+#[derive(Clone, Copy)]
+pub struct LodLevel {
+    pub min_size: f64,
+    pub lod: Lod,
 }
 
+pub const FULL_LODS: &[LodLevel] = &[
+    LodLevel { min_size: 0.0, lod: Lod::Mesh },
+];
+
+pub const SHIP_LODS: &[LodLevel] = &[
+    LodLevel { min_size: 3.0, lod: Lod::Mesh },
+    LodLevel { min_size: 0.0, lod: Lod::Point },
+];
+
+pub const PLANET_LODS: &[LodLevel] = &[
+    LodLevel { min_size: 4.0, lod: Lod::Mesh },
+    LodLevel { min_size: 0.0, lod: Lod::Point },
+];
+
+pub const ASTEROID_LODS: &[LodLevel] = &[
+    LodLevel { min_size: 6.0, lod: Lod::Mesh },
+    LodLevel { min_size: 2.0, lod: Lod::Wireframe },
+    LodLevel { min_size: 0.1, lod: Lod::Point },
+    LodLevel { min_size: 0.0, lod: Lod::Skip },
+];
+
 pub struct Particle {
     pub object: Object,
+    pub velocity: DVec3,
     pub lifetime: f64,
 }
 
+// generated is the index in the freshly generated system while nothing has hit or mined the asteroid,
+// saves regenerate those from the seed instead of storing them
 pub struct Asteroid {
+    pub generated: Option<usize>,
     pub object: Object,
     pub rotation_axis: DVec3,
     pub rotation_speed: f64,
+    pub integrity: f64,
+    pub resource: Resource,
+    pub ore: f64,
+    pub orbit: Option<Orbit>,
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct RenderStats {
+    pub drawn: usize,
+    pub culled: usize,
+}
+
+impl Pose {
+    pub fn lerp(self, other: Pose, t: f64) -> Pose {
+        Pose {
+            position: self.position.lerp(other.position, t),
+            rotation: self.rotation.slerp(other.rotation, t),
+        }
+    }
 }
 
 impl Game {
     pub fn new() -> Self {
-        Self {
+        Self::with_seed(rand::random())
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
+        let galaxy = generate_galaxy(&mut rng);
+        let (stars, planet, asteroids) = generate_system(&galaxy[0]);
+        let dust = generate_dust(&mut rng);
+        let mut collision_grid = SpatialHash::new(COLLISION_CELL_SIZE);
+        update_collision_grid(&mut collision_grid, &asteroids);
+        let mut game = Self {
+            seed,
+            stars,
+            dust,
+            asteroids,
+            planet,
+            galaxy,
+            system: 0,
+            system_ticks: 0,
+            transit: 0.0,
+            target_lock: None,
+            destination: None,
+            bookmarks: Vec::new(),
+            collision_grid,
+            rng,
+            tick_rate: TICK_RATE,
+            accumulator: 0.0,
+            previous_ship: Pose { position: DVec3::ZERO, rotation: DQuat::IDENTITY },
+            previous_camera: Pose { position: DVec3::ZERO, rotation: DQuat::IDENTITY },
             ship: Ship {
                 position: DVec3::ZERO,
                 velocity: DVec3::ZERO,
                 acceleration: DVec3::ZERO,
                 rotation: DQuat::IDENTITY,
-                angular_velocity: DQuat::IDENTITY,
-                angular_acceleration: DQuat::IDENTITY,
+                angular_velocity: DVec3::ZERO,
+                angular_acceleration: DVec3::ZERO,
                 thrust: enum_map! {_ => 0.0},
                 boost: 0.0,
                 boost_cooldown: 0.0,
                 brake: false,
+                autopilot: Autopilot::Off,
+                integrity: 100.0,
+                shield: 50.0,
+                shield_delay: 0.0,
+                destroyed: false,
+                respawn_timer: 0.0,
+                weapon: Weapon::Laser,
+                firing: false,
+                weapon_cooldown: 0.0,
+                laser: None,
+                cargo: enum_map! {_ => 0.0},
+                mining: None,
                 jumping: false,
                 charging_jump: false,
                 jump_charge: -1.0,
+                jump_aligned: true,
                 hull: Object {
-                    mesh: Rc::new(hull_mesh()),
+                    mesh: Rc::new(Mesh::new(hull_mesh())),
                     model: DMat4::IDENTITY,
                     color: 0xffffffff,
                     fill: 0x000000ff,
+                    lods: SHIP_LODS,
                 },
                 thrusters: create_thrusters(),
                 stats: ShipStats {
@@ -125,33 +330,125 @@ impl Game {
                     boost_cooldown: 1.0,
                     jump_speed: 18000.0,
                     jump_charge: 3.0,
+                    inertia: DMat3::from_diagonal(DVec3::new(0.8, 1.2, 1.0)),
+                    collision_radius: 2.5,
+                    max_integrity: 100.0,
+                    max_shield: 50.0,
+                    shield_regen: 10.0,
+                    shield_delay: 3.0,
+                    integrity_regen: 1.0,
+                    g_tolerance: 200.0,
+                    g_damage: 0.05,
+                    respawn_time: 3.0,
+                    laser_range: 2000.0,
+                    laser_dps: 40.0,
+                    cannon_speed: 500.0,
+                    cannon_damage: 50.0,
+                    cannon_rate: 4.0,
+                    cannon_lifetime: 4.0,
+                    mass: 50.0,
+                    cargo_capacity: 100.0,
+                    mining_range: 300.0,
+                    mining_rate: 2.0,
                 },
             },
             camera: Camera {
                 position: DVec3::ZERO,
                 rotation: DQuat::IDENTITY,
                 fov: 90.0,
+                aspect: WIDTH as f64 / HEIGHT as f64,
                 model: DMat4::IDENTITY,
                 view: DMat4::IDENTITY,
+                frustum: [DVec4::ZERO; 6],
             },
-            stars: generate_stars(),
-            dust: generate_dust(),
+            camera_rig: CameraRig::default(),
             particles: Vec::new(),
-            asteroids: generate_asteroids(),
+            projectiles: Vec::new(),
+            render_stats: RenderStats::default(),
+            key_labels: key_labels(&default_bindings()),
+            input: PlayerInput::default(),
+            recording: None,
+            replay: None,
+        };
+        spawn_in_orbit(&mut game.ship, &game.planet);
+        game
+    }
+
+    // advances the simulation in fixed ticks, leftover time is used to interpolate when drawing
+    pub fn step(&mut self, frame_dt: f64) {
+        let tick = 1.0 / self.tick_rate;
+        self.accumulator += f64::min(frame_dt, MAX_FRAME_TIME);
+        while self.accumulator >= tick {
+            self.update(tick);
+            self.accumulator -= tick;
         }
     }
 
     pub fn update(&mut self, dt: f64) {
+        self.previous_ship = Pose { position: self.ship.position, rotation: self.ship.rotation };
+        self.previous_camera = Pose { position: self.camera.position, rotation: self.camera.rotation };
+
+        // a replay overrides the live input until it runs out
+        if let Some(replay) = &mut self.replay {
+            match next_replay_input(replay) {
+                Some(input) => self.input = input,
+                None => self.replay = None,
+            }
+        }
+        if let Some(recording) = &mut self.recording {
+            record_input(recording, self.input);
+        }
+        self.apply_input();
+
+        self.ship.velocity += gravity(&self.planet, self.ship.position) * dt;
+        let reference = self.target_lock.map(|i| target_reference(&self.asteroids[i], &self.planet));
+        update_autopilot(&mut self.ship, reference);
+        let waypoint = self.destination.map(|d| destination_waypoint(d, self.ship.position, &self.planet, &self.bookmarks, &self.galaxy, self.system));
+        update_navigation(&mut self.ship, waypoint, dt);
         update_ship_movement(&mut self.ship, dt);
-        update_camera_position(&mut self.camera, &self.ship);
+
+        match self.destination {
+            Some(Destination::System(i)) if self.ship.jumping => {
+                self.transit += dt;
+                if self.transit >= SYSTEM_TRANSIT_TIME {
+                    self.enter_system(i);
+                }
+            }
+            _ => self.transit = 0.0,
+        }
+
+        for asteroid in &mut self.asteroids {
+            asteroid.object.model *= DMat4::from_axis_angle(asteroid.rotation_axis, asteroid.rotation_speed * dt);
+        }
+        self.system_ticks += 1;
+        update_asteroid_orbits(&mut self.asteroids, &self.planet, &mut self.collision_grid, dt);
+        resolve_ship_collisions(&mut self.ship, &self.asteroids, &self.planet, &self.collision_grid);
+
+        let was_destroyed = self.ship.destroyed;
+        update_ship_integrity(&mut self.ship, dt);
+        if self.ship.destroyed && !was_destroyed {
+            add_explosion_particles(&mut self.particles, self.ship.position, self.ship.velocity, self.ship.stats.collision_radius, &mut self.rng);
+        }
+        if was_destroyed && !self.ship.destroyed {
+            spawn_in_orbit(&mut self.ship, &self.planet);
+        }
+
+        update_weapons(&mut self.ship, &mut self.projectiles, &mut self.asteroids, &self.collision_grid, dt);
+        update_projectiles(&mut self.projectiles, &mut self.asteroids, &self.collision_grid, dt);
+        break_asteroids(&mut self.asteroids, &self.planet, &mut self.collision_grid, &mut self.target_lock, &mut self.particles, &mut self.rng);
+
+        update_camera_position(&mut self.camera, &self.camera_rig, &self.ship, dt);
 
         for star in &mut self.stars {
             star.model = DMat4::from_translation(self.camera.position);
         }
-        update_dust(&mut self.dust, self.camera.position, false);
+        // around the ship rather than the camera, so the orbit and free cameras don't change what is drawn
+        // from the rng and recordings replay the same with any camera
+        update_dust(&mut self.dust, self.ship.position, false, &mut self.rng);
 
         for particle in &mut self.particles {
             particle.lifetime -= dt;
+            particle.object.model = DMat4::from_translation(particle.velocity * dt) * particle.object.model;
             if particle.lifetime < 1.0 {
                 let (r, g, b, a) = color_to_float(particle.object.color);
                 let brightness = f64::max(0.0, particle.lifetime);
@@ -160,99 +457,203 @@ impl Game {
         }
         self.particles.retain(|p| p.lifetime > 0.0);
 
-        add_exhaust_particles(&mut self.particles, &self.ship, dt);
+        if !self.ship.destroyed {
+            add_exhaust_particles(&mut self.particles, &self.ship, dt, &mut self.rng);
+        }
 
         for (_thrust, thruster) in &mut self.ship.thrusters {
             thruster.model = self.ship.hull.model;
         }
+    }
 
-        for asteroid in &mut self.asteroids {
-            asteroid.object.model *= DMat4::from_axis_angle(asteroid.rotation_axis, asteroid.rotation_speed * dt);
+    // presses are cleared once applied, held controls stay until main fills the input again
+    pub fn apply_input(&mut self) {
+        let input = self.input;
+        apply_ship_input(&mut self.ship, &input);
+        if input.next_weapon {
+            self.ship.weapon = self.ship.weapon.next();
+        }
+        if input.cycle_target {
+            self.target_lock = cycle_target(self.target_lock, self.ship.position, &self.asteroids, &self.collision_grid);
+        }
+        if input.clear_target {
+            self.target_lock = None;
+        }
+        if let Some(mode) = input.autopilot {
+            self.ship.autopilot = if self.ship.autopilot == mode {Autopilot::Off} else {mode};
+        }
+        if input.cycle_destination && !self.ship.jumping {
+            let destinations = available_destinations(&self.planet, &self.bookmarks, &self.galaxy, self.system);
+            self.destination = cycle_destination(self.destination, &destinations);
+        }
+        if input.bookmark {
+            self.bookmarks.push(Bookmark { system: self.system, position: self.ship.position });
         }
+        self.input = PlayerInput {
+            thrust: input.thrust,
+            brake: input.brake,
+            firing: input.firing,
+            ..Default::default()
+        };
+    }
+
+    // unloads the current system and generates the arrival system, the ship drops out at its origin
+    pub fn enter_system(&mut self, index: usize) {
+        let (stars, planet, asteroids) = generate_system(&self.galaxy[index]);
+        self.stars = stars;
+        self.planet = planet;
+        self.asteroids = asteroids;
+        update_collision_grid(&mut self.collision_grid, &self.asteroids);
+        self.system = index;
+        self.system_ticks = 0;
+        self.transit = 0.0;
+        self.target_lock = None;
+        self.destination = None;
+        self.particles.clear();
+        self.projectiles.clear();
+        self.dust = generate_dust(&mut self.rng);
+
+        self.ship.jumping = false;
+        end_jump(&mut self.ship);
+        self.ship.position = DVec3::ZERO;
+        spawn_in_orbit(&mut self.ship, &self.planet);
+        self.camera.position = self.ship.position;
+        self.previous_ship = Pose { position: self.ship.position, rotation: self.ship.rotation };
+        self.previous_camera = Pose { position: self.camera.position, rotation: self.camera.rotation };
     }
 
-    pub fn draw(&self, frame: &mut [u8], depth: &mut [f64], dt: f64) {
+    pub fn draw(&mut self, target: &mut RenderTarget, dt: f64) {
         if self.ship.jumping {
-            clear_depth(depth);
-            clear_fade(frame, 0.95);
+            clear_depth(target);
+            clear_fade(target, 0.95);
         } else {
-            clear(frame, depth, 0x000000ff);
+            clear(target, 0x000000ff);
         }
 
+        let alpha = f64::clamp(self.accumulator * self.tick_rate, 0.0, 1.0);
+        let ship = self.previous_ship.lerp(Pose { position: self.ship.position, rotation: self.ship.rotation }, alpha);
+        let ship_model = DMat4::from_rotation_translation(ship.rotation, ship.position);
+        let mut camera = self.camera.clone();
+        let pose = self.previous_camera.lerp(Pose { position: self.camera.position, rotation: self.camera.rotation }, alpha);
+        set_camera_pose(&mut camera, pose.position, pose.rotation);
+        camera.aspect = target.aspect();
+        camera.frustum = frustum_planes(&camera);
+
+        let mut stats = RenderStats::default();
+        let mut count = |drawn: bool| if drawn {stats.drawn += 1} else {stats.culled += 1};
+
         for star in &self.stars {
-            draw_object(frame, depth, star, &self.camera);
+            count(draw_object(target, star, &camera));
         }
         for dust in &self.dust {
             if self.ship.velocity.length() < self.ship.stats.jump_speed / 2.0 {
                 let trail = -self.ship.velocity * 0.005;
                 if trail.length() > 0.1 {
-                    draw_line_3d(frame, depth, dust.model.transform_point3(DVec3::ZERO), dust.model.transform_point3(DVec3::ZERO) + trail, &self.camera, dust.color);
+                    draw_line_3d(target, dust.model.transform_point3(DVec3::ZERO), dust.model.transform_point3(DVec3::ZERO) + trail, &camera, dust.color);
                 }
             }
-            draw_object(frame, depth, &dust, &self.camera);
+            count(draw_object(target, &dust, &camera));
         }
         for particle in &self.particles {
-            draw_object(frame, depth, &particle.object, &self.camera);
+            count(draw_object(target, &particle.object, &camera));
+        }
+        for projectile in &self.projectiles {
+            count(draw_object(target, &projectile.object, &camera));
         }
         for asteroid in &self.asteroids {
-            draw_object(frame, depth, &asteroid.object, &self.camera);
+            count(draw_object(target, &asteroid.object, &camera));
+        }
+        if !self.ship.jumping && !self.ship.destroyed {
+            let trajectory = predict_trajectory(&self.planet, ship.position, self.ship.velocity);
+            for segment in trajectory.windows(2) {
+                draw_line_3d(target, segment[0], segment[1], &camera, 0x4080ffff);
+            }
+        }
+        if let Some(end) = self.ship.laser {
+            let color = if self.ship.weapon == Weapon::Miner {0xffff00ff} else {0xff00ffff};
+            draw_line_3d(target, ship_model.transform_point3(MUZZLE_OFFSET), end, &camera, color);
         }
 
-        draw_object(frame, depth, &self.ship.hull, &self.camera);
-        for (thrust, thruster) in &self.ship.thrusters {
-            if self.ship.thrust[thrust] > 0.01 {
-                draw_object(frame, depth, thruster, &self.camera);
+        // from the cockpit the hull would cover the whole view
+        if !self.ship.destroyed && self.camera_rig.mode != CameraMode::Cockpit {
+            count(draw_object(target, &Object { model: ship_model, ..self.ship.hull.clone() }, &camera));
+            for (thrust, thruster) in &self.ship.thrusters {
+                if (matches!(thrust, Thrust::Front) && self.ship.boost > 0.0) || self.ship.thrust[thrust] > 0.01 {
+                    count(draw_object(target, &Object { model: ship_model, ..thruster.clone() }, &camera));
+                }
             }
         }
+        self.render_stats = stats;
+
+        draw_line_3d(target, ship.position, ship.position + DVec3::new(1.0, 0.0, 0.0), &camera, 0xff0000ff);
+        draw_line_3d(target, ship.position, ship.position + DVec3::new(0.0, 1.0, 0.0), &camera, 0x00ff00ff);
+        draw_line_3d(target, ship.position, ship.position + DVec3::new(0.0, 0.0, 1.0), &camera, 0x0000ffff);
 
-        draw_line_3d(frame, depth, self.ship.position, self.ship.position + DVec3::new(1.0, 0.0, 0.0), &self.camera, 0xff0000ff);
-        draw_line_3d(frame, depth, self.ship.position, self.ship.position + DVec3::new(0.0, 1.0, 0.0), &self.camera, 0x00ff00ff);
-        draw_line_3d(frame, depth, self.ship.position, self.ship.position + DVec3::new(0.0, 0.0, 1.0), &self.camera, 0x0000ffff);
+        if let Some(i) = self.target_lock {
+            let (center, radius) = object_bounds(&self.asteroids[i].object);
+            draw_target_marker(target, center, radius, &camera, TARGET_COLOR);
+        }
+        if let Some(destination) = self.destination {
+            let waypoint = destination_waypoint(destination, self.ship.position, &self.planet, &self.bookmarks, &self.galaxy, self.system);
+            draw_target_marker(target, waypoint.position, 0.0, &camera, WAYPOINT_COLOR);
+        }
 
-        self.draw_hud(frame, depth, dt);
+        self.draw_hud(target, dt);
     }
 
-    pub fn draw_hud(&self, frame: &mut [u8], depth: &mut [f64], dt: f64) {
+    pub fn draw_hud(&self, target: &mut RenderTarget, dt: f64) {
+        // single letter boxes show the first character of longer key labels
         for (thrust, t) in self.ship.thrust {
-            let (x0, y0, x1, y1, key) = match thrust {
-                Thrust::Left => (0, 7, 6, 13, "A"),
-                Thrust::Right => (14, 7, 20, 13, "D"),
-                Thrust::Up => (21, 14, 27, 20, "R"),
-                Thrust::Down => (21, 7, 27, 13, "F"),
-                Thrust::Front => (7, 14, 13, 20, "W"),
-                Thrust::Back => (7, 7, 13, 13, "S"),
-                Thrust::YawLeft => (35, 7, 41, 13, "J"),
-                Thrust::YawRight => (49, 7, 55, 13, "L"),
-                Thrust::PitchUp => (42, 7, 48, 13, "K"),
-                Thrust::PitchDown => (42, 14, 48, 20, "I"),
-                Thrust::RollCCW => (35, 14, 41, 20, "U"),
-                Thrust::RollCW => (49, 14, 55, 20, "O"),
+            let (x0, y0, x1, y1) = match thrust {
+                Thrust::Left => (0, 7, 6, 13),
+                Thrust::Right => (14, 7, 20, 13),
+                Thrust::Up => (21, 14, 27, 20),
+                Thrust::Down => (21, 7, 27, 13),
+                Thrust::Front => (7, 14, 13, 20),
+                Thrust::Back => (7, 7, 13, 13),
+                Thrust::YawLeft => (35, 7, 41, 13),
+                Thrust::YawRight => (49, 7, 55, 13),
+                Thrust::PitchUp => (42, 7, 48, 13),
+                Thrust::PitchDown => (42, 14, 48, 20),
+                Thrust::RollCCW => (35, 14, 41, 20),
+                Thrust::RollCW => (49, 14, 55, 20),
             };
+            let key = fit_label(&self.key_labels[thrust_action(thrust)], 1);
             let bg: u32 = if t > 0.01 {0xffffffff} else {0x00000000};
             let fg: u32 = if t > 0.01 {0x00000000} else {0xffffffff};
-            draw_rectangle_fill(frame, depth, DVec3::new(x0 as f64, y0 as f64, 0.0), DVec3::new(x1 as f64, y1 as f64, 0.0), bg);
-            draw_text(frame, depth, DVec3::new((x0 + 1) as f64, (y0 + 1) as f64, 0.0), key, &FONT_5PX, 6, 1, fg);
+            draw_rectangle_fill(target, DVec3::new(x0 as f64, y0 as f64, 0.0), DVec3::new(x1 as f64, y1 as f64, 0.0), bg);
+            draw_text(target, DVec3::new((x0 + 1) as f64, (y0 + 1) as f64, 0.0), key, &FONT_5PX, 6, 1, fg);
         }
-        draw_rectangle_fill(frame, depth, DVec3::new(21.0, 0.0, 0.0), DVec3::new(55.0, 6.0, 0.0), if self.ship.brake {0xffffffff} else {0x000000ff});
-        draw_text(frame, depth, DVec3::new(22.0, 1.0, 0.0), "SPACE", &FONT_5PX, 7, 1, if self.ship.brake {0x000000ff} else {0xffffffff});
+        draw_rectangle_fill(target, DVec3::new(21.0, 0.0, 0.0), DVec3::new(55.0, 6.0, 0.0), if self.ship.brake {0xffffffff} else {0x000000ff});
+        draw_text(target, DVec3::new(22.0, 1.0, 0.0), fit_label(&self.key_labels[Action::Brake], 5), &FONT_5PX, 7, 1, if self.ship.brake {0x000000ff} else {0xffffffff});
 
-        draw_rectangle_fill(frame, depth, DVec3::new(0.0, 21.0, 0.0), DVec3::new(20.0, 27.0, 0.0), if self.ship.boost > 0.0 {0xffffffff} else {0x000000ff});
-        draw_text(frame, depth, DVec3::new(1.0, 22.0, 0.0), "TAB", &FONT_5PX, 7, 1, if self.ship.boost > 0.0 {0x000000ff} else {0xffffffff});
+        draw_rectangle_fill(target, DVec3::new(0.0, 21.0, 0.0), DVec3::new(20.0, 27.0, 0.0), if self.ship.boost > 0.0 {0xffffffff} else {0x000000ff});
+        draw_text(target, DVec3::new(1.0, 22.0, 0.0), fit_label(&self.key_labels[Action::Boost], 3), &FONT_5PX, 7, 1, if self.ship.boost > 0.0 {0x000000ff} else {0xffffffff});
 
-        draw_rectangle_fill(frame, depth, DVec3::new(0.0, 0.0, 0.0), DVec3::new(20.0, 6.0, 0.0), if self.ship.jumping || self.ship.charging_jump {0xffffffff} else {0x000000ff});
-        draw_text(frame, depth, DVec3::new(1.0, 1.0, 0.0), "ALT", &FONT_5PX, 7, 1, if self.ship.jumping|| self.ship.charging_jump {0x000000ff} else {0xffffffff});
+        draw_rectangle_fill(target, DVec3::new(0.0, 0.0, 0.0), DVec3::new(20.0, 6.0, 0.0), if self.ship.jumping || self.ship.charging_jump {0xffffffff} else {0x000000ff});
+        draw_text(target, DVec3::new(1.0, 1.0, 0.0), fit_label(&self.key_labels[Action::Jump], 3), &FONT_5PX, 7, 1, if self.ship.jumping|| self.ship.charging_jump {0x000000ff} else {0xffffffff});
     
-        draw_text(frame, depth, DVec3::new(1.0, (HEIGHT - 6) as f64, 0.0), &(f64::round(dt * 1000.0) / 1000.0).to_string(), &FONT_5PX, 6, 1, 0xffffffff);
-        draw_text(frame, depth, DVec3::new((WIDTH - 6 * 7) as f64, (HEIGHT - 6) as f64, 0.0), &"//exo83", &FONT_5PX, 6, 1, 0xffffffff);
+        draw_text(target, DVec3::new(1.0, (target.height - 6) as f64, 0.0), &(f64::round(dt * 1000.0) / 1000.0).to_string(), &FONT_5PX, 6, 1, 0xffffffff);
+        let render_stats = format!("{}/{}", self.render_stats.drawn, self.render_stats.drawn + self.render_stats.culled);
+        draw_text(target, DVec3::new(1.0, (target.height - 13) as f64, 0.0), &render_stats, &FONT_5PX, 6, 1, 0xffffffff);
+        draw_text(target, DVec3::new((target.width - 6 * 7) as f64, (target.height - 6) as f64, 0.0), &"//exo83", &FONT_5PX, 6, 1, 0xffffffff);
+        let seed = format!("#{}", self.seed);
+        draw_text(target, DVec3::new(target.width as f64 - (seed.len() * 6) as f64, (target.height - 13) as f64, 0.0), &seed, &FONT_5PX, 6, 1, 0xffffffff);
+        let system = &self.galaxy[self.system];
+        draw_text(target, DVec3::new(target.width as f64 - (system.name.len() * 6) as f64, (target.height - 20) as f64, 0.0), &system.name, FONT_5PX, 6, 1, system.star_color);
+        if self.camera_rig.mode != CameraMode::Chase {
+            let camera = self.camera_rig.mode.name();
+            draw_text(target, DVec3::new(target.width as f64 - (camera.len() * 6) as f64, (target.height - 27) as f64, 0.0), camera, FONT_5PX, 6, 1, 0xffffffff);
+        }
 
         let velocity = format!("{:.3} m/s  ", f64::round(self.ship.velocity.length() * 1000.0) / 1000.0);
         let acceleration = format!("{:.3} m/s^2", f64::round(self.ship.acceleration.length() * 1000.0) / 1000.0);
-        draw_text(frame, depth, DVec3::new(WIDTH as f64 - (velocity.len() * 6) as f64, 8.0, 0.0), &velocity, &FONT_5PX, 6, 1, 0xffffffff);
-        draw_text(frame, depth, DVec3::new(WIDTH as f64 - (acceleration.len() * 6) as f64, 1.0, 0.0), &acceleration, &FONT_5PX, 6, 1, 0xffffffff);
+        draw_text(target, DVec3::new(target.width as f64 - (velocity.len() * 6) as f64, 8.0, 0.0), &velocity, &FONT_5PX, 6, 1, 0xffffffff);
+        draw_text(target, DVec3::new(target.width as f64 - (acceleration.len() * 6) as f64, 1.0, 0.0), &acceleration, &FONT_5PX, 6, 1, 0xffffffff);
 
         let boost_cooldown = format!("{:.2}", f64::round(self.ship.boost_cooldown * 100.0) / 100.0);
-        draw_rectangle_fill(frame, depth, DVec3::new(28.0, 21.0, 0.0), DVec3::new(55.0, 27.0, 0.0), if self.ship.boost_cooldown > 0.0 {0xffffffff} else {0x000000ff});
-        draw_text(frame, depth, DVec3::new(57.0 as f64 - (boost_cooldown.len() * 7) as f64, 22.0, 0.0), &boost_cooldown, &FONT_5PX, 7, 1, if self.ship.boost_cooldown > 0.0 {0x000000ff} else {0xffffffff});
+        draw_rectangle_fill(target, DVec3::new(28.0, 21.0, 0.0), DVec3::new(55.0, 27.0, 0.0), if self.ship.boost_cooldown > 0.0 {0xffffffff} else {0x000000ff});
+        draw_text(target, DVec3::new(57.0 as f64 - (boost_cooldown.len() * 7) as f64, 22.0, 0.0), &boost_cooldown, &FONT_5PX, 7, 1, if self.ship.boost_cooldown > 0.0 {0x000000ff} else {0xffffffff});
 
         if self.ship.charging_jump {
             let jump_charge = if self.ship.jump_charge <= 1.0 {
@@ -264,7 +665,82 @@ impl Game {
             } else {
                 format!("    {:.2}    ", f64::round(self.ship.jump_charge * 100.0) / 100.0)
             };
-            draw_text(frame, depth, DVec3::new(WIDTH as f64 / 2.0 + 48.0 + 96.0 - (jump_charge.len() * 6*4) as f64, HEIGHT as f64 - 48.0, 0.0), &jump_charge, &FONT_5PX, 6, 4, 0xffffffff);
+            draw_text(target, DVec3::new(target.width as f64 / 2.0 + 48.0 + 96.0 - (jump_charge.len() * 6*4) as f64, target.height as f64 - 48.0, 0.0), &jump_charge, &FONT_5PX, 6, 4, 0xffffffff);
+        }
+
+        draw_rectangle_fill(target, DVec3::new(0.0, 28.0, 0.0), DVec3::new(6.0, 34.0, 0.0), if self.ship.firing {0xffffffff} else {0x00000000});
+        draw_text(target, DVec3::new(1.0, 29.0, 0.0), fit_label(&self.key_labels[Action::Fire], 1), FONT_5PX, 6, 1, if self.ship.firing {0x00000000} else {0xffffffff});
+        draw_text(target, DVec3::new(9.0, 29.0, 0.0), self.ship.weapon.name(), FONT_5PX, 6, 1, 0xffffffff);
+        if self.ship.autopilot != Autopilot::Off {
+            draw_text(target, DVec3::new(1.0, 36.0, 0.0), &format!("AP {}", self.ship.autopilot.name()), FONT_5PX, 6, 1, 0xffffffff);
+        }
+
+        if let Some(destination) = self.destination {
+            let distance = match destination {
+                Destination::System(i) => format!("{:.1} ly", system_distance(&self.galaxy, self.system, i)),
+                _ => {
+                    let waypoint = destination_waypoint(destination, self.ship.position, &self.planet, &self.bookmarks, &self.galaxy, self.system);
+                    format!("{:.1} km", (waypoint.position - self.ship.position).length() / 1000.0)
+                }
+            };
+            let nav = format!("NAV {} {}", destination.name(&self.galaxy), distance);
+            draw_text(target, DVec3::new(target.width as f64 / 2.0 - (nav.len() * 6) as f64 / 2.0, (target.height - 6) as f64, 0.0), &nav, FONT_5PX, 6, 1, WAYPOINT_COLOR);
+        }
+        if let Some(i) = self.target_lock {
+            draw_target_info(target, &target_info(i, &self.ship, &self.asteroids, &self.planet));
+        }
+
+        let bar_x = target.width as f64 / 2.0 - 36.0;
+        let cargo = cargo_mass(&self.ship.cargo);
+        draw_text(target, DVec3::new(bar_x - 26.0, 15.0, 0.0), "CRGO", FONT_5PX, 6, 1, 0xffffffff);
+        draw_bar(target, DVec3::new(bar_x, 14.0, 0.0), 96.0, cargo / self.ship.stats.cargo_capacity, 0xffff00ff);
+        let mut y = target.height as f64 - 27.0;
+        for (resource, amount) in &self.ship.cargo {
+            if *amount > 0.0 {
+                draw_text(target, DVec3::new(1.0, y, 0.0), &format!("{} {:.1}t", resource.name(), amount), FONT_5PX, 6, 1, 0xffffffff);
+                y -= 7.0;
+            }
+        }
+        if let Some((resource, ore)) = self.ship.mining {
+            let mining = format!("{} {:.1}t", resource.name(), ore);
+            draw_text(target, DVec3::new(target.width as f64 / 2.0 - (mining.len() * 6) as f64 / 2.0, target.height as f64 / 2.0 + 30.0, 0.0), &mining, FONT_5PX, 6, 1, 0xffff00ff);
+        }
+        draw_text(target, DVec3::new(bar_x - 26.0, 8.0, 0.0), "SHLD", FONT_5PX, 6, 1, 0xffffffff);
+        draw_bar(target, DVec3::new(bar_x, 7.0, 0.0), 96.0, self.ship.shield / self.ship.stats.max_shield, 0xffffffff);
+        draw_text(target, DVec3::new(bar_x - 26.0, 1.0, 0.0), "HULL", FONT_5PX, 6, 1, 0xffffffff);
+        draw_bar(target, DVec3::new(bar_x, 0.0, 0.0), 96.0, self.ship.integrity / self.ship.stats.max_integrity, if self.ship.integrity < self.ship.stats.max_integrity / 4.0 {0xff00ffff} else {0xffffffff});
+
+        if self.ship.destroyed {
+            let respawn = format!("DESTROYED {:.1}", self.ship.respawn_timer);
+            draw_text(target, DVec3::new(target.width as f64 / 2.0 - (respawn.len() * 6*2) as f64 / 2.0, target.height as f64 / 2.0 + 20.0, 0.0), &respawn, FONT_5PX, 6, 2, 0xffffffff);
+        }
+    }
+}
+
+// rejects zero, negative and NaN rates, with those the fixed step loop never runs or never ends
+pub fn valid_tick_rate(tick_rate: f64) -> bool {
+    (MIN_TICK_RATE..=MAX_TICK_RATE).contains(&tick_rate)
+}
+
+// thrust overwrites whatever the autopilot and brake left from the last tick
+pub fn apply_ship_input(ship: &mut Ship, input: &PlayerInput) {
+    ship.thrust = input.thrust;
+    ship.brake = input.brake && !ship.jumping;
+    ship.firing = input.firing;
+    if input.boost && !ship.jumping && ship.boost_cooldown == 0.0 {
+        ship.boost = ship.stats.boost_strength;
+        ship.boost_cooldown = ship.stats.boost_cooldown;
+    }
+    if input.jump {
+        if !ship.jumping {
+            ship.charging_jump = !ship.charging_jump;
+            ship.jump_charge = match ship.charging_jump {
+                true => ship.stats.jump_charge,
+                false => 0.0,
+            }
+        } else {
+            ship.jumping = false;
+            end_jump(ship);
         }
     }
 }
@@ -272,17 +748,19 @@ impl Game {
 pub fn update_ship_movement(ship: &mut Ship, dt: f64) {
     if ship.charging_jump {
         ship.brake = true;
-        ship.jump_charge = f64::max(0.0, ship.jump_charge - dt);
         ship.thrust[Thrust::Front] = 2.0 * ship.stats.thrust;
-        if ship.jump_charge == 0.0 {
-            ship.charging_jump = false;
-            ship.jumping = true;
-            start_jump(ship, dt);
+        if ship.jump_aligned {
+            ship.jump_charge = f64::max(0.0, ship.jump_charge - dt);
+            if ship.jump_charge == 0.0 {
+                ship.charging_jump = false;
+                ship.jumping = true;
+                start_jump(ship);
+            }
         }
     }
 
     if ship.brake {
-        let angular_brake_thrust = DVec3::from(ship.angular_velocity.inverse().to_euler(glam::EulerRot::XYZ)) * 200.0;
+        let angular_brake_thrust = ship.stats.inertia * -ship.angular_velocity * 4.0;
         if ship.thrust[Thrust::PitchUp] == 0.0 && ship.thrust[Thrust::PitchDown] == 0.0 {ship.thrust[Thrust::PitchUp] = f64::clamp(angular_brake_thrust.x, 0.0, ship.stats.angular_thrust);}
         if ship.thrust[Thrust::PitchUp] == 0.0 && ship.thrust[Thrust::PitchDown] == 0.0 {ship.thrust[Thrust::PitchDown] = f64::clamp(-angular_brake_thrust.x, 0.0, ship.stats.angular_thrust);}
         if ship.thrust[Thrust::YawLeft] == 0.0 && ship.thrust[Thrust::YawRight] == 0.0 {ship.thrust[Thrust::YawLeft] = f64::clamp(angular_brake_thrust.y, 0.0, ship.stats.angular_thrust);}
@@ -299,98 +777,176 @@ pub fn update_ship_movement(ship: &mut Ship, dt: f64) {
         if ship.thrust[Thrust::Back] == 0.0 && ship.thrust[Thrust::Front] == 0.0 {ship.thrust[Thrust::Front] = f64::clamp(brake_thrust.z, 0.0, 2.0 * ship.stats.thrust);}
     }
 
-    if ship.jumping {
+    if ship.jumping || ship.destroyed {
         ship.thrust = enum_map! {
             _ => 0.0,
         };
+        ship.boost = 0.0;
     }
 
-    ship.angular_acceleration = DQuat::from_euler(
-        glam::EulerRot::XYZ,
-        (ship.thrust[Thrust::PitchUp] - ship.thrust[Thrust::PitchDown]) * dt*dt, // todo: apply delta properly
-        (ship.thrust[Thrust::YawLeft] - ship.thrust[Thrust::YawRight]) * dt*dt,
-        (ship.thrust[Thrust::RollCCW] - ship.thrust[Thrust::RollCW]) * dt*dt,
+    // euler's equations in body space, torque from the thrusters minus the gyroscopic term
+    let torque = DVec3::new(
+        ship.thrust[Thrust::PitchUp] - ship.thrust[Thrust::PitchDown],
+        ship.thrust[Thrust::YawLeft] - ship.thrust[Thrust::YawRight],
+        ship.thrust[Thrust::RollCCW] - ship.thrust[Thrust::RollCW],
     );
-    ship.angular_velocity *= ship.angular_acceleration;
-    ship.rotation *= ship.angular_velocity;
+    let inertia = ship.stats.inertia;
+    ship.angular_acceleration = inertia.inverse() * (torque - ship.angular_velocity.cross(inertia * ship.angular_velocity));
+    ship.angular_velocity += ship.angular_acceleration * dt;
+    ship.rotation = (ship.rotation * DQuat::from_scaled_axis(ship.angular_velocity * dt)).normalize();
 
-    ship.thrust[Thrust::Front] += ship.boost;
+    // boost is added per tick rather than into the thrust map, which input only resets once per frame
+    let front_thrust = ship.thrust[Thrust::Front] + ship.boost;
     ship.boost = f64::max(0.0, ship.boost - ship.stats.boost_strength / ship.stats.boost_duration * dt);
     ship.boost_cooldown = f64::max(0.0, ship.boost_cooldown - dt);
     
+    // thrust values are the accelerations of the empty ship, cargo adds mass
+    let mass_factor = ship.stats.mass / (ship.stats.mass + cargo_mass(&ship.cargo));
     ship.acceleration = ship.rotation * DVec3::new(
         ship.thrust[Thrust::Right] - ship.thrust[Thrust::Left],
         ship.thrust[Thrust::Up] - ship.thrust[Thrust::Down],
-        ship.thrust[Thrust::Back] - ship.thrust[Thrust::Front],
-    );
+        ship.thrust[Thrust::Back] - front_thrust,
+    ) * mass_factor;
     ship.velocity += ship.acceleration * dt;
     ship.position += ship.velocity * dt;
 
     ship.hull.model = DMat4::from_rotation_translation(ship.rotation, ship.position);
 }
 
-pub fn update_camera_position(camera: &mut Camera, ship: &Ship) {
-    let position_offset = DVec3::new(0.0, 4.0, 10.0);
-    let rotation_offset = DVec3::new(0.0, 0.0, 0.0);
-    let trailing_factor = 0.85;
+// shields regenerate after a delay without damage, the hull slowly repairs once shields are full
+pub fn update_ship_integrity(ship: &mut Ship, dt: f64) {
+    if ship.destroyed {
+        ship.respawn_timer = f64::max(0.0, ship.respawn_timer - dt);
+        if ship.respawn_timer == 0.0 {
+            respawn_ship(ship);
+        }
+        return;
+    }
+
+    let g_excess = ship.acceleration.length() - ship.stats.g_tolerance;
+    if g_excess > 0.0 {
+        apply_damage(ship, g_excess * ship.stats.g_damage * dt, false);
+    }
+    if ship.destroyed {
+        return;
+    }
+
+    ship.shield_delay = f64::max(0.0, ship.shield_delay - dt);
+    if ship.shield_delay == 0.0 {
+        if ship.shield < ship.stats.max_shield {
+            ship.shield = f64::min(ship.stats.max_shield, ship.shield + ship.stats.shield_regen * dt);
+        } else {
+            ship.integrity = f64::min(ship.stats.max_integrity, ship.integrity + ship.stats.integrity_regen * dt);
+        }
+    }
+}
+
+// shields absorb damage first unless bypassed, e.g. by g-forces acting on the hull itself
+pub fn apply_damage(ship: &mut Ship, damage: f64, shielded: bool) {
+    if ship.destroyed || damage <= 0.0 {
+        return;
+    }
+    ship.shield_delay = ship.stats.shield_delay;
+    let mut damage = damage;
+    if shielded {
+        let absorbed = f64::min(ship.shield, damage);
+        ship.shield -= absorbed;
+        damage -= absorbed;
+    }
+    ship.integrity = f64::max(0.0, ship.integrity - damage);
+    if ship.integrity == 0.0 {
+        destroy_ship(ship);
+    }
+}
+
+pub fn destroy_ship(ship: &mut Ship) {
+    if ship.jumping {
+        ship.jumping = false;
+        end_jump(ship);
+    }
+    ship.destroyed = true;
+    ship.respawn_timer = ship.stats.respawn_time;
+    ship.charging_jump = false;
+    ship.jump_charge = -1.0;
+    ship.shield = 0.0;
+}
+
+pub fn respawn_ship(ship: &mut Ship) {
+    ship.destroyed = false;
+    ship.position = DVec3::ZERO;
+    ship.velocity = DVec3::ZERO;
+    ship.acceleration = DVec3::ZERO;
+    ship.rotation = DQuat::IDENTITY;
+    ship.angular_velocity = DVec3::ZERO;
+    ship.angular_acceleration = DVec3::ZERO;
+    ship.boost = 0.0;
+    ship.boost_cooldown = 0.0;
+    ship.integrity = ship.stats.max_integrity;
+    ship.shield = ship.stats.max_shield;
+    ship.shield_delay = 0.0;
+    ship.hull.model = DMat4::from_rotation_translation(ship.rotation, ship.position);
+}
 
-    camera.position = camera.position * trailing_factor + (ship.position + ship.rotation * position_offset) * (1.0 - trailing_factor);
-    camera.rotation = DQuat::look_at_rh(camera.position, ship.position + ship.rotation * rotation_offset, ship.rotation * DVec3::new(0.0, 1.0, 0.0)).inverse();
+pub fn set_camera_pose(camera: &mut Camera, position: DVec3, rotation: DQuat) {
+    camera.position = position;
+    camera.rotation = rotation;
     camera.model = DMat4::from_rotation_translation(camera.rotation, camera.position);
     camera.view = camera.model.inverse();
 }
 
-pub fn start_jump(ship: &mut Ship, dt: f64) {
+pub fn start_jump(ship: &mut Ship) {
     ship.thrust = enum_map! {
         _ => 0.0,
     };
-    ship.angular_velocity = DQuat::from_euler(glam::EulerRot::XYZ, 0.0, 0.0, 1.0 * dt);
+    ship.angular_velocity = DVec3::new(0.0, 0.0, 1.0);
     ship.velocity = ship.rotation * DVec3::new(0.0, 0.0, -ship.stats.jump_speed);
 }
 
 pub fn end_jump(ship: &mut Ship) {
-    ship.angular_velocity = DQuat::from_euler(glam::EulerRot::XYZ, 0.0, 0.0, 0.0);
+    ship.angular_velocity = DVec3::ZERO;
     ship.velocity = ship.rotation * DVec3::new(0.0, 0.0, -100.0);
 }
 
-pub fn generate_stars() -> Vec<Object> {
+pub fn generate_stars(rng: &mut impl Rng) -> Vec<Object> {
     let count = 1000;
 
     let mut stars = Vec::new();
     for _ in 0..count {
         let pos = DVec3::new(
-            rand::rng().sample::<f64, StandardNormal>(StandardNormal), 
-            rand::rng().sample::<f64, StandardNormal>(StandardNormal), 
-            rand::rng().sample::<f64, StandardNormal>(StandardNormal),
+            rng.sample::<f64, StandardNormal>(StandardNormal), 
+            rng.sample::<f64, StandardNormal>(StandardNormal), 
+            rng.sample::<f64, StandardNormal>(StandardNormal),
         ).normalize() * FAR / 2.0;
-        let b = (rand::rng().random::<f64>() * 255.0) as u32 & 0xff;
+        let b = (rng.random::<f64>() * 255.0) as u32 & 0xff;
         let col = (b << 24) | (b << 16) | (b << 8) | 0xff;
         stars.push(Object {
-            mesh: Rc::new(vec![vec![pos]]),
+            mesh: Rc::new(Mesh::new(vec![vec![pos]])),
             model: DMat4::IDENTITY,
             color: col,
             fill: 0x00000000,
+            lods: FULL_LODS,
         });
     }
     stars
 }
 
-pub fn update_dust(dust: &mut Vec<Object>, center: DVec3, first: bool) {
+pub fn update_dust(dust: &mut Vec<Object>, center: DVec3, first: bool, rng: &mut impl Rng) {
     let count: usize = 200;
     let (min_dist, max_dist): (f64, f64) = (90.0, 100.0);
 
     dust.retain(|d| (d.model.transform_point3(DVec3::ZERO) - center).length() <= max_dist);
     while dust.len() < count {
         let offset = DVec3::new(
-            rand::rng().sample::<f64, StandardNormal>(StandardNormal), 
-            rand::rng().sample::<f64, StandardNormal>(StandardNormal), 
-            rand::rng().sample::<f64, StandardNormal>(StandardNormal),
-        ).normalize() * rand::rng().random_range(if first {0.0} else {min_dist.powf(3.0)}..=max_dist.powf(3.0)).powf(1.0/3.0);
+            rng.sample::<f64, StandardNormal>(StandardNormal), 
+            rng.sample::<f64, StandardNormal>(StandardNormal), 
+            rng.sample::<f64, StandardNormal>(StandardNormal),
+        ).normalize() * rng.random_range(if first {0.0} else {min_dist.powf(3.0)}..=max_dist.powf(3.0)).powf(1.0/3.0);
         dust.push(Object {
-            mesh: Rc::new(vec![vec![DVec3::ZERO]]),
+            mesh: Rc::new(Mesh::new(vec![vec![DVec3::ZERO]])),
             model: DMat4::from_translation(center + offset),
             color: 0xffffffff,
             fill: 0x00000000,
+            lods: FULL_LODS,
         });
     }
     for d in dust {
@@ -399,9 +955,9 @@ pub fn update_dust(dust: &mut Vec<Object>, center: DVec3, first: bool) {
     }
 }
 
-pub fn generate_dust() -> Vec<Object> {
+pub fn generate_dust(rng: &mut impl Rng) -> Vec<Object> {
     let mut dust = Vec::new();
-    update_dust(&mut dust, DVec3::ZERO, true);
+    update_dust(&mut dust, DVec3::ZERO, true, rng);
     dust
 }
 
@@ -410,22 +966,24 @@ pub fn create_thrusters() -> EnumMap<Thrust, Object> {
 
     let thrusters = enum_map! {
         Thrust::Front => Object {
-            mesh: Rc::new(front_thruster_mesh()),
+            mesh: Rc::new(Mesh::new(front_thruster_mesh())),
             model: DMat4::IDENTITY,
             color: color,
             fill: 0x000000ff,
+            lods: SHIP_LODS,
         },
         _ => Object {
-            mesh: Rc::new(vec![]),
+            mesh: Rc::new(Mesh::new(vec![])),
             model: DMat4::IDENTITY,
             color: color,
             fill: 0x00000000,
+            lods: FULL_LODS,
         },
     };
     thrusters
 }
 
-pub fn add_exhaust_particles(particles: &mut Vec<Particle>, ship: &Ship, dt: f64) {
+pub fn add_exhaust_particles(particles: &mut Vec<Particle>, ship: &Ship, dt: f64, rng: &mut impl Rng) {
     let acceleration_factor = f64::clamp(ship.acceleration.length() / 100.0, 0.0, 1.0);
     let velocity_factor = f64::clamp(ship.velocity.length() / 200.0, 0.1, 1.0);
     let particle_strength = if !ship.jumping {(acceleration_factor * 2.0 + velocity_factor) / 3.0} else {1.0};
@@ -437,21 +995,23 @@ pub fn add_exhaust_particles(particles: &mut Vec<Particle>, ship: &Ship, dt: f64
 
     for _ in 0..5 {
         let particle_offset = DVec3::new(
-            rand::rng().sample::<f64, StandardNormal>(StandardNormal), 
-            rand::rng().sample::<f64, StandardNormal>(StandardNormal), 
-            rand::rng().sample::<f64, StandardNormal>(StandardNormal),
+            rng.sample::<f64, StandardNormal>(StandardNormal), 
+            rng.sample::<f64, StandardNormal>(StandardNormal), 
+            rng.sample::<f64, StandardNormal>(StandardNormal),
         ).normalize() * 0.5;
 
         for pos in &thruster_positions {
-            if rand::random::<f64>() < particle_strength {
-                let translation = ship.position + particle_offset + ship.rotation * *pos - ship.velocity * dt * rand::random::<f64>();
+            if rng.random::<f64>() < particle_strength {
+                let translation = ship.position + particle_offset + ship.rotation * *pos - ship.velocity * dt * rng.random::<f64>();
                 particles.push(Particle {
                     object: Object {
-                        mesh: Rc::new(vec![vec![DVec3::ZERO]]),
+                        mesh: Rc::new(Mesh::new(vec![vec![DVec3::ZERO]])),
                         model: DMat4::from_translation(translation),
                         color: 0xff00ffff,
                         fill: 0x00000000,
+                        lods: FULL_LODS,
                     },
+                    velocity: DVec3::ZERO,
                     lifetime: 10.0, 
                 });
             }
@@ -459,44 +1019,92 @@ pub fn add_exhaust_particles(particles: &mut Vec<Particle>, ship: &Ship, dt: f64
     }
 }
 
-pub fn generate_asteroids() -> Vec<Asteroid> {
-    let count = 20000;
-    let (min_dist, max_dist): (f64, f64) = (60000.0, 120000.0);
+pub fn add_explosion_particles(particles: &mut Vec<Particle>, position: DVec3, velocity: DVec3, radius: f64, rng: &mut impl Rng) {
+    let count = 300;
+    let max_speed = radius * 10.0;
+
+    for _ in 0..count {
+        let direction = DVec3::new(
+            rng.sample::<f64, StandardNormal>(StandardNormal), 
+            rng.sample::<f64, StandardNormal>(StandardNormal), 
+            rng.sample::<f64, StandardNormal>(StandardNormal),
+        ).normalize();
+        particles.push(Particle {
+            object: Object {
+                mesh: Rc::new(Mesh::new(vec![vec![DVec3::ZERO]])),
+                model: DMat4::from_translation(position + direction * rng.random::<f64>() * radius),
+                color: if rng.random::<f64>() < 0.5 {0xff00ffff} else {0xffffffff},
+                fill: 0x00000000,
+                lods: FULL_LODS,
+            },
+            velocity: velocity + direction * rng.random::<f64>() * max_speed,
+            lifetime: rng.random_range(1.0..4.0),
+        });
+    }
+}
+
+// a ring close to the planet and a wider, thicker belt further out, the origin lies within the ring's radius
+pub fn generate_asteroids(system: &StarSystem, rng: &mut impl Rng) -> (Planet, Vec<Asteroid>) {
+    let planet_scale = system.planet_scale;
+    let ring_count = if system.ring {20000} else {0};
+    let belt_count = if system.belt {5000} else {0};
+    let ring = (2.0 * planet_scale, 4.0 * planet_scale);
+    let belt = (5.0 * planet_scale, 6.5 * planet_scale);
     let (min_scale, max_scale): (f64, f64) = (1.0, 100.0);
-    let ring_plane_rotation = DMat4::from_axis_angle(DVec3::new(rand::random::<f64>(), rand::random::<f64>(), rand::random::<f64>()).normalize(), rand::random::<f64>() * PI);
-    let mesh = Rc::new(parse_obj(ASTEROID_OBJ));
-    let center = ring_plane_rotation.transform_point3(DVec3::new(0.0, 0.0, 1.0).normalize() * 100000.0);
+    let ring_plane_rotation = DMat4::from_axis_angle(DVec3::new(rng.random::<f64>(), rng.random::<f64>(), rng.random::<f64>()).normalize(), rng.random::<f64>() * PI);
+    let mesh = Rc::new(Mesh::new(parse_obj(ASTEROID_OBJ)));
+    let center = ring_plane_rotation.transform_point3(DVec3::new(0.0, 0.0, 1.0).normalize() * 3.0 * planet_scale);
 
-    let mut asteroids = Vec::with_capacity(count+1);
-    let planet_scale = 30000.0;
+    let mut asteroids = Vec::with_capacity(ring_count + belt_count + 1);
     asteroids.push(Asteroid {
+        generated: Some(0),
         object: Object {
-            mesh: Rc::new(parse_obj(PLANET_OBJ)),
+            mesh: Rc::new(Mesh::new(parse_obj(PLANET_OBJ))),
             model: DMat4::from_translation(center) * DMat4::from_scale(DVec3::ONE * planet_scale),
             color: 0xffffffff,
             fill: 0x000000ff,
+            lods: PLANET_LODS,
         },
         rotation_axis: ring_plane_rotation.transform_point3(DVec3::new(0.0, 1.0, 0.0)),
         rotation_speed: 0.1,
+        integrity: f64::INFINITY,
+        resource: Resource::Ice,
+        ore: 0.0,
+        orbit: None,
     });
-    for _ in 0..count {
+    let planet = Planet {
+        position: center,
+        mass: PLANET_MASS * (planet_scale / PLANET_SCALE).powi(2),
+        radius: object_bounds(&asteroids[0].object).1,
+        axis: asteroids[0].rotation_axis,
+        ring: if system.ring {Some(ring)} else {None},
+    };
+    for i in 0..ring_count + belt_count {
+        let ((min_dist, max_dist), thickness) = if i < ring_count {(ring, 0.01)} else {(belt, 0.03)};
         let offset = ring_plane_rotation.transform_point3((DVec3::new(
-            rand::rng().sample::<f64, StandardNormal>(StandardNormal), 
-            rand::rng().sample::<f64, StandardNormal>(StandardNormal), 
-            rand::rng().sample::<f64, StandardNormal>(StandardNormal),
-        ) * DVec3::new(1.0, 0.01, 1.0)).normalize() * rand::rng().random_range(min_dist.powf(2.0)..max_dist.powf(2.0)).powf(1.0/2.0));
-        let scale = rand::random_range(min_scale..max_scale);
+            rng.sample::<f64, StandardNormal>(StandardNormal), 
+            rng.sample::<f64, StandardNormal>(StandardNormal), 
+            rng.sample::<f64, StandardNormal>(StandardNormal),
+        ) * DVec3::new(1.0, thickness, 1.0)).normalize() * rng.random_range(min_dist.powf(2.0)..max_dist.powf(2.0)).powf(1.0/2.0));
+        let scale = rng.random_range(min_scale..max_scale);
+        let velocity = circular_velocity(&planet, center + offset, planet.axis) * rng.random_range(0.97..1.03);
 
         asteroids.push(Asteroid {
+            generated: Some(asteroids.len()),
             object: Object {
                 mesh: mesh.clone(),
                 model: DMat4::from_translation(center + offset) * DMat4::from_scale(DVec3::ONE * scale),
                 color: 0xffffffff,
                 fill: 0x000000ff,
+                lods: ASTEROID_LODS,
             },
-            rotation_axis: DVec3::new(rand::random_range(-1.0..1.0), rand::random_range(-1.0..1.0), rand::random_range(-1.0..1.0)).normalize(),
-            rotation_speed: rand::random_range(-1.0..1.0),
+            rotation_axis: DVec3::new(rng.random_range(-1.0..1.0), rng.random_range(-1.0..1.0), rng.random_range(-1.0..1.0)).normalize(),
+            rotation_speed: rng.random_range(-1.0..1.0),
+            integrity: scale * ASTEROID_INTEGRITY,
+            resource: generate_resource(rng),
+            ore: scale * ASTEROID_ORE,
+            orbit: orbit_from_state(planet.mu(), offset, velocity),
         });
     }
-    asteroids
+    (planet, asteroids)
 }
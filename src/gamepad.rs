@@ -0,0 +1,252 @@
+use enum_map::{enum_map, Enum, EnumMap};
+use log::warn;
+
+use crate::controls::*;
+use crate::game::*;
+use crate::save::*;
+
+// file name on native, local storage key on wasm
+pub const GAMEPAD_NAME: &str = "gamepad.cfg";
+
+pub const DEFAULT_DEADZONE: f64 = 0.15;
+pub const DEFAULT_CURVE: f64 = 2.0;
+
+// the standard layout, sticks are -1..1 with right and up positive, triggers 0..1
+#[derive(Enum, Clone, Copy)]
+pub enum PadAxis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+#[derive(Enum, Clone, Copy)]
+pub enum PadButton {
+    South,
+    East,
+    West,
+    North,
+    LeftBumper,
+    RightBumper,
+    Select,
+    Start,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+// the sticks and triggers drive the translation, yaw and pitch channels proportionally, see apply_gamepad_thrust
+pub const PAD_BUTTONS: &[(PadButton, Action)] = &[
+    (PadButton::South, Action::Fire),
+    (PadButton::East, Action::Brake),
+    (PadButton::West, Action::Boost),
+    (PadButton::North, Action::Jump),
+    (PadButton::LeftBumper, Action::RollCCW),
+    (PadButton::RightBumper, Action::RollCW),
+    (PadButton::Select, Action::AutopilotOff),
+    (PadButton::Start, Action::KillRotation),
+    (PadButton::DPadUp, Action::NextWeapon),
+    (PadButton::DPadDown, Action::ClearTarget),
+    (PadButton::DPadLeft, Action::CycleDestination),
+    (PadButton::DPadRight, Action::CycleTarget),
+];
+
+// axis values below the deadzone are dropped, the rest is rescaled to 0..1 and raised to the curve exponent
+// so small deflections give finer control
+pub struct GamepadSettings {
+    pub deadzone: f64,
+    pub curve: f64,
+    pub invert_pitch: bool,
+}
+
+// state of the first connected gamepad, polled once per input update
+pub struct Gamepad {
+    #[cfg(all(not(target_arch = "wasm32"), feature = "gamepad"))]
+    gilrs: Option<gilrs::Gilrs>,
+    pub connected: bool,
+    pub axes: EnumMap<PadAxis, f64>,
+    pub buttons: EnumMap<PadButton, bool>,
+    pub previous: EnumMap<PadButton, bool>,
+}
+
+impl Default for GamepadSettings {
+    fn default() -> Self {
+        GamepadSettings {
+            deadzone: DEFAULT_DEADZONE,
+            curve: DEFAULT_CURVE,
+            invert_pitch: false,
+        }
+    }
+}
+
+impl Default for Gamepad {
+    fn default() -> Self {
+        Gamepad {
+            #[cfg(all(not(target_arch = "wasm32"), feature = "gamepad"))]
+            gilrs: gilrs::Gilrs::new().inspect_err(|err| warn!("gamepad: {err}")).ok(),
+            connected: false,
+            axes: enum_map! {_ => 0.0},
+            buttons: enum_map! {_ => false},
+            previous: enum_map! {_ => false},
+        }
+    }
+}
+
+impl Gamepad {
+    pub fn poll(&mut self) {
+        self.previous = self.buttons;
+        #[cfg(all(not(target_arch = "wasm32"), feature = "gamepad"))]
+        let state = self.gilrs.as_mut().and_then(read_gamepad);
+        #[cfg(any(target_arch = "wasm32", not(feature = "gamepad")))]
+        let state = read_gamepad();
+        self.connected = state.is_some();
+        (self.axes, self.buttons) = state.unwrap_or((enum_map! {_ => 0.0}, enum_map! {_ => false}));
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        PAD_BUTTONS.iter().any(|&(button, a)| a == action && self.buttons[button] && !self.previous[button])
+    }
+
+    pub fn held(&self, action: Action) -> bool {
+        PAD_BUTTONS.iter().any(|&(button, a)| a == action && self.buttons[button])
+    }
+}
+
+// gilrs only updates its cached state while events are drained
+#[cfg(all(not(target_arch = "wasm32"), feature = "gamepad"))]
+fn read_gamepad(gilrs: &mut gilrs::Gilrs) -> Option<(EnumMap<PadAxis, f64>, EnumMap<PadButton, bool>)> {
+    use gilrs::{Axis, Button};
+    while gilrs.next_event().is_some() {}
+    let (_, pad) = gilrs.gamepads().next()?;
+    let axis = |axis| pad.value(axis) as f64;
+    let trigger = |button| pad.button_data(button).map_or(0.0, |data| data.value() as f64);
+    let axes = enum_map! {
+        PadAxis::LeftX => axis(Axis::LeftStickX),
+        PadAxis::LeftY => axis(Axis::LeftStickY),
+        PadAxis::RightX => axis(Axis::RightStickX),
+        PadAxis::RightY => axis(Axis::RightStickY),
+        PadAxis::LeftTrigger => trigger(Button::LeftTrigger2),
+        PadAxis::RightTrigger => trigger(Button::RightTrigger2),
+    };
+    let buttons = enum_map! {
+        PadButton::South => pad.is_pressed(Button::South),
+        PadButton::East => pad.is_pressed(Button::East),
+        PadButton::West => pad.is_pressed(Button::West),
+        PadButton::North => pad.is_pressed(Button::North),
+        PadButton::LeftBumper => pad.is_pressed(Button::LeftTrigger),
+        PadButton::RightBumper => pad.is_pressed(Button::RightTrigger),
+        PadButton::Select => pad.is_pressed(Button::Select),
+        PadButton::Start => pad.is_pressed(Button::Start),
+        PadButton::DPadUp => pad.is_pressed(Button::DPadUp),
+        PadButton::DPadDown => pad.is_pressed(Button::DPadDown),
+        PadButton::DPadLeft => pad.is_pressed(Button::DPadLeft),
+        PadButton::DPadRight => pad.is_pressed(Button::DPadRight),
+    };
+    Some((axes, buttons))
+}
+
+// built without the gamepad feature there is nothing to read on native
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "gamepad")))]
+fn read_gamepad() -> Option<(EnumMap<PadAxis, f64>, EnumMap<PadButton, bool>)> {
+    None
+}
+
+// the browser's standard mapping has the stick y axes pointing down
+#[cfg(target_arch = "wasm32")]
+fn read_gamepad() -> Option<(EnumMap<PadAxis, f64>, EnumMap<PadButton, bool>)> {
+    use wasm_bindgen::JsCast;
+    let pads = web_sys::window()?.navigator().get_gamepads().ok()?;
+    let pad = pads.iter().find_map(|pad| pad.dyn_into::<web_sys::Gamepad>().ok())?;
+    let (pad_axes, pad_buttons) = (pad.axes(), pad.buttons());
+    let axis = |i| pad_axes.get(i).as_f64().unwrap_or(0.0);
+    let button = |i| pad_buttons.get(i).dyn_into::<web_sys::GamepadButton>().ok();
+    let pressed = |i| button(i).is_some_and(|b| b.pressed());
+    let trigger = |i| button(i).map_or(0.0, |b| b.value());
+    let axes = enum_map! {
+        PadAxis::LeftX => axis(0),
+        PadAxis::LeftY => -axis(1),
+        PadAxis::RightX => axis(2),
+        PadAxis::RightY => -axis(3),
+        PadAxis::LeftTrigger => trigger(6),
+        PadAxis::RightTrigger => trigger(7),
+    };
+    let buttons = enum_map! {
+        PadButton::South => pressed(0),
+        PadButton::East => pressed(1),
+        PadButton::West => pressed(2),
+        PadButton::North => pressed(3),
+        PadButton::LeftBumper => pressed(4),
+        PadButton::RightBumper => pressed(5),
+        PadButton::Select => pressed(8),
+        PadButton::Start => pressed(9),
+        PadButton::DPadUp => pressed(12),
+        PadButton::DPadDown => pressed(13),
+        PadButton::DPadLeft => pressed(14),
+        PadButton::DPadRight => pressed(15),
+    };
+    Some((axes, buttons))
+}
+
+pub fn shape_axis(value: f64, settings: &GamepadSettings) -> f64 {
+    let magnitude = ((value.abs() - settings.deadzone) / (1.0 - settings.deadzone)).clamp(0.0, 1.0);
+    magnitude.powf(settings.curve) * value.signum()
+}
+
+// left stick strafes and moves forward, right stick yaws and pitches with forward pushing the nose down,
+// triggers move up and down. keyboard thrust already set on a channel wins if it is stronger
+pub fn apply_gamepad_thrust(input: &mut PlayerInput, stats: &ShipStats, gamepad: &Gamepad, settings: &GamepadSettings) {
+    if !gamepad.connected {
+        return;
+    }
+    let axis = |axis| shape_axis(gamepad.axes[axis], settings);
+    let pitch = if settings.invert_pitch {-axis(PadAxis::RightY)} else {axis(PadAxis::RightY)};
+    let lift = axis(PadAxis::RightTrigger) - axis(PadAxis::LeftTrigger);
+    let (max, angular) = (stats.thrust, stats.angular_thrust);
+    add_thrust_axis(input, Thrust::Right, Thrust::Left, axis(PadAxis::LeftX), max, max);
+    add_thrust_axis(input, Thrust::Front, Thrust::Back, axis(PadAxis::LeftY), 2.0 * max, max);
+    add_thrust_axis(input, Thrust::Up, Thrust::Down, lift, max, max);
+    add_thrust_axis(input, Thrust::YawRight, Thrust::YawLeft, axis(PadAxis::RightX), angular, angular);
+    add_thrust_axis(input, Thrust::PitchDown, Thrust::PitchUp, pitch, angular, angular);
+}
+
+pub fn add_thrust_axis(input: &mut PlayerInput, positive: Thrust, negative: Thrust, value: f64, max_positive: f64, max_negative: f64) {
+    let thrust = &mut input.thrust[positive];
+    *thrust = f64::max(*thrust, value.max(0.0) * max_positive);
+    let thrust = &mut input.thrust[negative];
+    *thrust = f64::max(*thrust, (-value).max(0.0) * max_negative);
+}
+
+// one "setting = value" per line like the controls file, missing or broken lines keep their defaults
+pub fn parse_gamepad_settings(text: &str) -> GamepadSettings {
+    let mut settings = GamepadSettings::default();
+    for (name, value) in config_entries(text, "gamepad") {
+        let parsed = match name {
+            "deadzone" => value.parse().map(|v: f64| settings.deadzone = v.clamp(0.0, 0.99)).is_ok(),
+            "curve" => value.parse().map(|v: f64| settings.curve = v.max(0.1)).is_ok(),
+            "invert_pitch" => value.parse().map(|v| settings.invert_pitch = v).is_ok(),
+            _ => {
+                warn!("gamepad: unknown setting \"{name}\"");
+                continue;
+            }
+        };
+        if !parsed {
+            warn!("gamepad: bad value \"{value}\" for {name}");
+        }
+    }
+    settings
+}
+
+pub fn load_gamepad_settings() -> GamepadSettings {
+    load_config(GAMEPAD_NAME, "gamepad", parse_gamepad_settings, format_gamepad_settings, GamepadSettings::default())
+}
+
+pub fn format_gamepad_settings(settings: &GamepadSettings) -> String {
+    let mut text = String::from("# stick deadzone 0..1, response curve exponent (1 is linear), invert_pitch true or false\n");
+    text += &format!("deadzone = {}\n", settings.deadzone);
+    text += &format!("curve = {}\n", settings.curve);
+    text += &format!("invert_pitch = {}\n", settings.invert_pitch);
+    text
+}
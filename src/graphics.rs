@@ -1,79 +1,102 @@
 use core::f64;
 use std::cmp::{max, min};
-use std::collections::{HashMap, HashSet};
-use fxhash::FxBuildHasher;
 use std::mem::swap;
-use glam::DVec3;
-
-use crate::game::{Camera, Object};
-use crate::transform::{out_of_bounds, transform_mesh, transform_world_to_screen, FAR};
-use crate::{WIDTH, HEIGHT};
-
-pub fn clear(frame: &mut [u8], depth: &mut [f64], color: u32) {
-    for x in 0..WIDTH as i32 {
-        for y in 0..HEIGHT as i32 {
-            let i = (((HEIGHT as i32 - 1 - y) * WIDTH as i32 + x) * 4) as usize;
-            depth[i/4] = FAR;
-            frame[i] = (color >> 24) as u8;
-            frame[i+1] = (color >> 16) as u8;
-            frame[i+2] = (color >> 8) as u8;
-            frame[i+3] = (color) as u8;
+use glam::{DVec3, DVec4};
+
+use crate::game::{Camera, Lod, Object};
+use crate::transform::{clip_line, clip_polygon, inside_frustum, out_of_bounds, projected_size, sphere_in_frustum, transform_clip_to_screen, transform_mesh, transform_world_to_clip, FAR, MAX_CLIPPED, MAX_POLYGON};
+
+pub struct RenderTarget {
+    pub width: u32,
+    pub height: u32,
+    pub frame: Vec<u8>,
+    pub depth: Vec<f64>,
+}
+
+impl RenderTarget {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            frame: vec![0; (width * height * 4) as usize],
+            depth: vec![FAR; (width * height) as usize],
         }
     }
+
+    // index into frame of the pixel at (x, y), with y pointing up
+    pub fn index(&self, x: i32, y: i32) -> usize {
+        (((self.height as i32 - 1 - y) * self.width as i32 + x) * 4) as usize
+    }
+
+    pub fn aspect(&self) -> f64 {
+        self.width as f64 / self.height as f64
+    }
 }
 
-pub fn clear_depth(depth: &mut [f64]) {
-    for x in 0..WIDTH as i32 {
-        for y in 0..HEIGHT as i32 {
-            let i = (((HEIGHT as i32 - 1 - y) * WIDTH as i32 + x) * 4) as usize;
-            depth[i/4] = FAR;
+pub fn clear(target: &mut RenderTarget, color: u32) {
+    for x in 0..target.width as i32 {
+        for y in 0..target.height as i32 {
+            let i = target.index(x, y);
+            target.depth[i/4] = FAR;
+            target.frame[i] = (color >> 24) as u8;
+            target.frame[i+1] = (color >> 16) as u8;
+            target.frame[i+2] = (color >> 8) as u8;
+            target.frame[i+3] = (color) as u8;
         }
     }
 }
 
-pub fn clear_fade(frame: &mut [u8], f: f64) {
-    for x in 0..WIDTH as i32 {
-        for y in 0..HEIGHT as i32 {
-            let i = (((HEIGHT as i32 - 1 - y) * WIDTH as i32 + x) * 4) as usize;
+pub fn clear_depth(target: &mut RenderTarget) {
+    for x in 0..target.width as i32 {
+        for y in 0..target.height as i32 {
+            let i = target.index(x, y);
+            target.depth[i/4] = FAR;
+        }
+    }
+}
+
+pub fn clear_fade(target: &mut RenderTarget, f: f64) {
+    for x in 0..target.width as i32 {
+        for y in 0..target.height as i32 {
+            let i = target.index(x, y);
 
             let mut color = 0x00000000;
-            color |= (frame[i] as u32) << 24;
-            color |= (frame[i+1] as u32) << 16;
-            color |= (frame[i+2] as u32) << 8;
-            color |= frame[i+3] as u32;
+            color |= (target.frame[i] as u32) << 24;
+            color |= (target.frame[i+1] as u32) << 16;
+            color |= (target.frame[i+2] as u32) << 8;
+            color |= target.frame[i+3] as u32;
 
             let c = color_to_float(color);
             color = float_to_color((c.0 * f, c.1 * f, c.2 * f, c.3));
 
-            frame[i] = (color >> 24) as u8;
-            frame[i+1] = (color >> 16) as u8;
-            frame[i+2] = (color >> 8) as u8;
-            frame[i+3] = (color) as u8;
+            target.frame[i] = (color >> 24) as u8;
+            target.frame[i+1] = (color >> 16) as u8;
+            target.frame[i+2] = (color >> 8) as u8;
+            target.frame[i+3] = (color) as u8;
         }
     }
 }
 
-pub fn draw_pixel(frame: &mut [u8], depth: &mut [f64], p: DVec3, color: u32) {
-    if out_of_bounds(p, 0) { return; };
-    let (x, y) = (p.x as i32, p.y as i32);
-    let i = (((HEIGHT as i32 - 1 - y) * WIDTH as i32 + x) * 4) as usize;
-    if p.z <= depth[i/4] {
+pub fn draw_pixel(target: &mut RenderTarget, p: DVec3, color: u32) {
+    if out_of_bounds(p, 0, target) { return; };
+    let i = target.index(p.x as i32, p.y as i32);
+    if p.z <= target.depth[i/4] {
         // color = float_to_color(f64::clamp((p.z - 0.975) * 40.0, 0.0, 1.0), 0.0, 0.0, 1.0);
-        depth[i/4] = p.z;
-        frame[i] = (color >> 24) as u8;
-        frame[i+1] = (color >> 16) as u8;
-        frame[i+2] = (color >> 8) as u8;
-        frame[i+3] = (color) as u8;
+        target.depth[i/4] = p.z;
+        target.frame[i] = (color >> 24) as u8;
+        target.frame[i+1] = (color >> 16) as u8;
+        target.frame[i+2] = (color >> 8) as u8;
+        target.frame[i+3] = (color) as u8;
     }
 }
 
-pub fn bresenham(p0: DVec3, p1: DVec3) -> Vec<DVec3> {
-    if out_of_bounds(p0, 0) && out_of_bounds(p1, 0) { return vec![]; };
+pub fn bresenham(target: &RenderTarget, p0: DVec3, p1: DVec3) -> Vec<DVec3> {
+    if out_of_bounds(p0, 0, target) && out_of_bounds(p1, 0, target) { return vec![]; };
     let (mut x0, mut y0, mut z0) = (p0.x as i32, p0.y as i32, p0.z);
     let (mut x1, mut y1, mut z1) = (p1.x as i32, p1.y as i32, p1.z);
 
     let length = max(i32::abs(y1 - y0), i32::abs(x1 - x0)) + 1;
-    if length > (WIDTH * 4) as i32 { return vec![]; };
+    if length > (target.width * 4) as i32 { return vec![]; };
     let mut line = Vec::with_capacity(length as usize);
 
     if i32::abs(y1 - y0) < i32::abs(x1 - x0) {
@@ -135,76 +158,89 @@ pub fn bresenham(p0: DVec3, p1: DVec3) -> Vec<DVec3> {
     line
 }
 
-pub fn map_lines(lines: &Vec<DVec3>) -> HashMap::<i32, (DVec3, DVec3), FxBuildHasher> {
-    let mut map_y = HashMap::<i32, (DVec3, DVec3), FxBuildHasher>::default();
-    for p in lines {
-        if map_y.contains_key(&(p.y as i32)) {
-            let e = map_y.get_mut(&(p.y as i32)).unwrap();
-            if p.x < e.0.x {
-                e.0 = *p;
-            }
-            if p.x > e.1.x {
-                e.1 = *p;
-            }
-        } else {
-            map_y.insert(p.y as i32, (*p, *p));
-        }
-    };
-    map_y
-}
+pub fn draw_line(target: &mut RenderTarget, p0: DVec3, p1: DVec3, color: u32) {
+    if out_of_bounds(p0, 0, target) && out_of_bounds(p1, 0, target) { return; };
 
-pub fn map_outline(outline_lines: &Vec<DVec3>) -> HashMap::<i32, HashSet<i32, FxBuildHasher>, FxBuildHasher> {
-    let mut outline_map_y = HashMap::<i32, HashSet<i32, FxBuildHasher>, FxBuildHasher>::default();
-    for p in outline_lines {
-        if outline_map_y.contains_key(&(p.y as i32)) {
-            outline_map_y.get_mut(&(p.y as i32)).unwrap().insert(p.x as i32);
-        } else {
-            outline_map_y.insert(p.y as i32, HashSet::<i32, FxBuildHasher>::from_iter([p.x as i32]));
-        }
-    };
-    outline_map_y
+    let line = bresenham(target, p0, p1);
+    for p in line {
+        draw_pixel(target, p, color);
+    }
 }
 
-pub fn draw_line(frame: &mut [u8], depth: &mut [f64], p0: DVec3, p1: DVec3, color: u32) {
-    if out_of_bounds(p0, 0) && out_of_bounds(p1, 0) { return; };
+pub fn edge_function(a: DVec3, b: DVec3, x: f64, y: f64) -> f64 {
+    (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+}
 
-    let line = bresenham(p0, p1);
-    for p in line {
-        draw_pixel(frame, depth, p, color);
+// edge function rasterizer with top-left fill rule. outline[i] marks the edge from p_i to p_i+1
+// as part of the polygon outline, pixels within half a pixel of it get the outline color
+pub fn draw_triangle_fill_outline(target: &mut RenderTarget, p0: DVec3, p1: DVec3, p2: DVec3, outline: [bool; 3], color: u32, fill: u32) {
+    let area = edge_function(p0, p1, p2.x, p2.y);
+    if area == 0.0 || area.is_nan() { return; };
+    let (v, outline) = if area > 0.0 {
+        ([p0, p1, p2], outline)
+    } else {
+        ([p0, p2, p1], [outline[2], outline[1], outline[0]])
+    };
+    let area = area.abs();
+
+    let x0 = max(f64::min(v[0].x, f64::min(v[1].x, v[2].x)) as i32 - 1, 0);
+    let x1 = min(f64::max(v[0].x, f64::max(v[1].x, v[2].x)) as i32 + 1, target.width as i32 - 1);
+    let y0 = max(f64::min(v[0].y, f64::min(v[1].y, v[2].y)) as i32 - 1, 0);
+    let y1 = min(f64::max(v[0].y, f64::max(v[1].y, v[2].y)) as i32 + 1, target.height as i32 - 1);
+    if x0 > x1 || y0 > y1 { return; };
+
+    let mut top_left = [false; 3];
+    let mut major = [0.0; 3];
+    let mut x_major = [false; 3];
+    let mut extent = [(0, 0); 3];
+    for i in 0..3 {
+        let (a, b) = (v[i], v[(i+1) % 3]);
+        let d = b - a;
+        top_left[i] = d.y < 0.0 || (d.y == 0.0 && d.x < 0.0);
+        major[i] = f64::max(d.x.abs(), d.y.abs());
+        x_major[i] = d.x.abs() >= d.y.abs();
+        extent[i] = if x_major[i] {
+            (f64::min(a.x, b.x) as i32, f64::max(a.x, b.x) as i32)
+        } else {
+            (f64::min(a.y, b.y) as i32, f64::max(a.y, b.y) as i32)
+        };
     }
-}
 
-pub fn draw_triangle_fill_outline(frame: &mut [u8], depth: &mut [f64], p0: DVec3, p1: DVec3, p2: DVec3, outline_lines: &Vec<DVec3>, color: u32, fill: u32) {
-    if out_of_bounds(p0, 0) && out_of_bounds(p1, 0) && out_of_bounds(p2, 0) { return; };
-    
-    let mut lines = vec![];
-    lines.append(&mut bresenham(p0, p1));
-    lines.append(&mut bresenham(p1, p2));
-    lines.append(&mut bresenham(p2, p0));
-
-    let map_y = map_lines(&lines);
-    let outline_map_y = map_outline(outline_lines);
-
-    for (y, v) in map_y {
-        let min = v.0;
-        let max = v.1;
-        let empty = HashSet::<i32, FxBuildHasher>::default();
-        let outline_x = outline_map_y.get(&y).unwrap_or(&empty);
-
-        for x in min.x as i32 ..= max.x as i32 {
-            let dx = (x as f64 - min.x) / f64::max(max.x - min.x, 1.0);
-            let z = min.z * (1.0 - dx) + max.z * dx;
-            // println!("({x}, {dx}, {z})");
-            if outline_x.contains(&x) {
-                draw_pixel(frame, depth, DVec3::new(x as f64, y as f64, z), color);
-            } else {
-                draw_pixel(frame, depth, DVec3::new(x as f64, y as f64, z), fill);
+    for y in y0..=y1 {
+        let py = y as f64 + 0.5;
+        let mut e = [
+            edge_function(v[0], v[1], x0 as f64 + 0.5, py),
+            edge_function(v[1], v[2], x0 as f64 + 0.5, py),
+            edge_function(v[2], v[0], x0 as f64 + 0.5, py),
+        ];
+        let step = [v[1].y - v[0].y, v[2].y - v[1].y, v[0].y - v[2].y];
+
+        for x in x0..=x1 {
+            let inside = (0..3).all(|i| e[i] > 0.0 || (e[i] == 0.0 && top_left[i]));
+            let on_outline = (0..3).any(|i| {
+                let t = if x_major[i] {x} else {y};
+                outline[i] && major[i] > 0.0 && e[i].abs() / major[i] <= 0.5 && t >= extent[i].0 && t <= extent[i].1
+            });
+            if inside || on_outline {
+                let z = (e[1] * v[0].z + e[2] * v[1].z + e[0] * v[2].z) / area;
+                draw_pixel(target, DVec3::new(x as f64, y as f64, z), if on_outline {color} else {fill});
+            }
+            for i in 0..3 {
+                e[i] -= step[i];
             }
         }
     }
 }
 
-pub fn draw_rectangle(frame: &mut [u8], depth: &mut [f64], p0: DVec3, p1: DVec3, color: u32) {
+pub fn draw_polygon_fill_outline(target: &mut RenderTarget, polygon: &[(DVec3, bool)], color: u32, fill: u32) {
+    let n = polygon.len();
+    for i in 2..n {
+        let outline = [i == 2 && polygon[0].1, polygon[i-1].1, i == n-1 && polygon[n-1].1];
+        draw_triangle_fill_outline(target, polygon[0].0, polygon[i-1].0, polygon[i].0, outline, color, fill);
+    }
+}
+
+pub fn draw_rectangle(target: &mut RenderTarget, p0: DVec3, p1: DVec3, color: u32) {
     let (mut x0, mut y0) = (p0.x as i32, p0.y as i32);
     let (mut x1, mut y1) = (p1.x as i32, p1.y as i32);
     if x1 < x0 {
@@ -215,19 +251,19 @@ pub fn draw_rectangle(frame: &mut [u8], depth: &mut [f64], p0: DVec3, p1: DVec3,
     }
 
     x0 = max(x0, -1);
-    x1 = min(x1, WIDTH as i32);
+    x1 = min(x1, target.width as i32);
     y0 = max(y0, -1);
-    y1 = min(y1, HEIGHT as i32);
+    y1 = min(y1, target.height as i32);
     for x in x0..=x1 {
         for y in y0..=y1 {
             if x == x0 || x == x1 || y == y0 || y == y1 {
-                draw_pixel(frame, depth, DVec3::new(x as f64, y as f64, p0.z), color);
+                draw_pixel(target, DVec3::new(x as f64, y as f64, p0.z), color);
             }
         }
     }
 }
 
-pub fn draw_rectangle_fill(frame: &mut [u8], depth: &mut [f64], p0: DVec3, p1: DVec3, color: u32) {
+pub fn draw_rectangle_fill(target: &mut RenderTarget, p0: DVec3, p1: DVec3, color: u32) {
     let (mut x0, mut y0) = (p0.x as i32, p0.y as i32);
     let (mut x1, mut y1) = (p1.x as i32, p1.y as i32);
     if x1 < x0 {
@@ -238,17 +274,35 @@ pub fn draw_rectangle_fill(frame: &mut [u8], depth: &mut [f64], p0: DVec3, p1: D
     }
 
     x0 = max(x0, -1);
-    x1 = min(x1, WIDTH as i32);
+    x1 = min(x1, target.width as i32);
     y0 = max(y0, -1);
-    y1 = min(y1, HEIGHT as i32);
+    y1 = min(y1, target.height as i32);
     for x in x0..=x1 {
         for y in y0..=y1 {
-            draw_pixel(frame, depth, DVec3::new(x as f64, y as f64, p0.z), color);
+            draw_pixel(target, DVec3::new(x as f64, y as f64, p0.z), color);
         }
     }
 }
 
-pub fn draw_sprite(frame: &mut [u8], depth: &mut [f64], p: DVec3, sprite: &[&[u8]], scale: i32, color: u32) {
+// outlined horizontal bar filled to fraction, 7 pixels tall like the hud key boxes
+pub fn draw_bar(target: &mut RenderTarget, p: DVec3, width: f64, fraction: f64, color: u32) {
+    draw_rectangle(target, p, p + DVec3::new(width, 6.0, 0.0), color);
+    let fill = f64::clamp(fraction, 0.0, 1.0) * (width - 4.0);
+    if fill >= 1.0 {
+        draw_rectangle_fill(target, p + DVec3::new(2.0, 2.0, 0.0), p + DVec3::new(1.0 + fill, 4.0, 0.0), color);
+    }
+}
+
+// square gate of the given half size with a center mark and the knob at the deflection, stick is -1..1 per axis
+pub fn draw_virtual_stick(target: &mut RenderTarget, p: DVec3, size: f64, stick: DVec3, color: u32) {
+    draw_rectangle(target, p - DVec3::new(size, size, 0.0), p + DVec3::new(size, size, 0.0), color);
+    draw_pixel(target, p, color);
+    let knob = p + DVec3::new((stick.x * size).round(), (stick.y * size).round(), 0.0);
+    draw_line(target, p, knob, color);
+    draw_rectangle_fill(target, knob - DVec3::new(1.0, 1.0, 0.0), knob + DVec3::new(1.0, 1.0, 0.0), color);
+}
+
+pub fn draw_sprite(target: &mut RenderTarget, p: DVec3, sprite: &[&[u8]], scale: i32, color: u32) {
     if scale < 0 { // todo
         return;
     }
@@ -257,7 +311,7 @@ pub fn draw_sprite(frame: &mut [u8], depth: &mut [f64], p: DVec3, sprite: &[&[u8
             if sprite[sprite.len() - 1 - i][j] == 1 {
                 for di in 0..scale {
                     for dj in 0..scale {
-                        draw_pixel(frame, depth, DVec3::new((p.x as i32 + scale * j as i32 + dj) as f64, (p.y as i32 + scale * i as i32 + di) as f64, p.z), color);
+                        draw_pixel(target, DVec3::new((p.x as i32 + scale * j as i32 + dj) as f64, (p.y as i32 + scale * i as i32 + di) as f64, p.z), color);
                     }
                 }
             }
@@ -265,12 +319,12 @@ pub fn draw_sprite(frame: &mut [u8], depth: &mut [f64], p: DVec3, sprite: &[&[u8
     }
 }
 
-pub fn draw_text(frame: &mut [u8], depth: &mut [f64], p: DVec3, text: &str, font: &[&[&[u8]]], offset: i32, scale: i32, color: u32) {
+pub fn draw_text(target: &mut RenderTarget, p: DVec3, text: &str, font: &[&[&[u8]]], offset: i32, scale: i32, color: u32) {
     let mut dx = 0;
     let mut dy = 0;
     for c in text.as_bytes() {
         if (*c as usize) < font.len() {
-            draw_sprite(frame, depth, DVec3::new(p.x + dx as f64, p.y + dy as f64, p.z), &font[*c as usize], scale, color);
+            draw_sprite(target, DVec3::new(p.x + dx as f64, p.y + dy as f64, p.z), &font[*c as usize], scale, color);
             dx += offset * scale;
             if *c == 10 { // LF
                 dx = 0;
@@ -280,72 +334,106 @@ pub fn draw_text(frame: &mut [u8], depth: &mut [f64], p: DVec3, text: &str, font
     }
 }
 
-pub fn draw_point_3d(frame: &mut [u8], depth: &mut [f64], v: DVec3, camera: &Camera, color: u32) {
-    let p = transform_world_to_screen(v, camera);
-    draw_pixel(frame, depth, p, color);
+pub fn draw_point_3d(target: &mut RenderTarget, v: DVec3, camera: &Camera, color: u32) {
+    let c = transform_world_to_clip(v, camera);
+    if !inside_frustum(c) { return; };
+    draw_pixel(target, transform_clip_to_screen(c, target), color);
+}
+
+pub fn draw_line_3d(target: &mut RenderTarget, v0: DVec3, v1: DVec3, camera: &Camera, color: u32) {
+    let c0 = transform_world_to_clip(v0, camera);
+    let c1 = transform_world_to_clip(v1, camera);
+    if let Some((c0, c1)) = clip_line(c0, c1) {
+        draw_line(target, transform_clip_to_screen(c0, target), transform_clip_to_screen(c1, target), color);
+    }
 }
 
-pub fn draw_line_3d(frame: &mut [u8], depth: &mut [f64], v0: DVec3, v1: DVec3, camera: &Camera, color: u32) {
-    let p0 = transform_world_to_screen(v0, camera);
-    let p1 = transform_world_to_screen(v1, camera);
-    draw_line(frame, depth, p0, p1, color);
+// vertices are in clip space with the outline flag of the edge to the next one
+pub fn draw_clipped_polygon(target: &mut RenderTarget, polygon: &[(DVec4, bool)], color: u32, fill: u32) {
+    let clipped = clip_polygon(polygon);
+    let mut points = [(DVec3::ZERO, false); MAX_CLIPPED];
+    for (point, (c, edge)) in points.iter_mut().zip(clipped.as_slice()) {
+        *point = (transform_clip_to_screen(*c, target), *edge);
+    }
+    draw_polygon_fill_outline(target, &points[..clipped.len], color, fill);
 }
 
-pub fn draw_triangle_fill_outline_3d(frame: &mut [u8], depth: &mut [f64], v0: DVec3, v1: DVec3, v2: DVec3, outline_lines: &Vec<DVec3>, camera: &Camera, color: u32, fill: u32) {
-    let p0 = transform_world_to_screen(v0, camera);
-    let p1 = transform_world_to_screen(v1, camera);
-    let p2 = transform_world_to_screen(v2, camera);
-    draw_triangle_fill_outline(frame, depth, p0, p1, p2, &outline_lines, color, fill);
+pub fn draw_triangle_fill_outline_3d(target: &mut RenderTarget, v0: DVec3, v1: DVec3, v2: DVec3, camera: &Camera, color: u32, fill: u32) {
+    draw_clipped_polygon(target, &[
+        (transform_world_to_clip(v0, camera), true),
+        (transform_world_to_clip(v1, camera), true),
+        (transform_world_to_clip(v2, camera), true),
+    ], color, fill);
 }
 
-pub fn draw_polygon_3d(frame: &mut [u8], depth: &mut [f64], polygon: &Vec<DVec3>, camera: &Camera, color: u32, fill: u32) {
+pub fn draw_polygon_3d(target: &mut RenderTarget, polygon: &Vec<DVec3>, camera: &Camera, color: u32, fill: u32) {
     if polygon.len() == 1 {
-        draw_point_3d(frame, depth, polygon[0], camera, color);
+        draw_point_3d(target, polygon[0], camera, color);
 
     } else if polygon.len() == 2 {
-        draw_line_3d(frame, depth, polygon[0], polygon[1], camera, color);
+        draw_line_3d(target, polygon[0], polygon[1], camera, color);
 
     } else if polygon.len() >= 3 {
         let normal = (polygon[1] - polygon[0]).cross(polygon[2] - polygon[0]).normalize() * 10.0;
         if normal.dot(camera.position - polygon[0]) >= 0.0 {
-            if fill != 0x00000000 {
-                let outline_points: Vec<DVec3> = polygon.iter().map(|v| transform_world_to_screen(*v, camera)).collect();
-                let mut outline_lines = vec![];
-                for i in 0..outline_points.len() {
-                    outline_lines.append(&mut bresenham(outline_points[i], outline_points[(i+1) % outline_points.len()]));
+            if fill != 0x00000000 && polygon.len() <= MAX_POLYGON {
+                let mut clip = [(DVec4::ZERO, true); MAX_POLYGON];
+                for (c, v) in clip.iter_mut().zip(polygon) {
+                    c.0 = transform_world_to_clip(*v, camera);
                 }
-                for i in 2..polygon.len() {
-                    let v0 = polygon[0];
-                    let v1 = polygon[i-1];
-                    let v2 = polygon[i];
-                    draw_triangle_fill_outline_3d(frame, depth, v0, v1, v2, &outline_lines, camera, color, fill);
+                draw_clipped_polygon(target, &clip[..polygon.len()], color, fill);
+            } else if fill != 0x00000000 {
+                // the diagonals of the fan aren't outlined
+                let n = polygon.len();
+                let c0 = transform_world_to_clip(polygon[0], camera);
+                for i in 2..n {
+                    draw_clipped_polygon(target, &[
+                        (c0, i == 2),
+                        (transform_world_to_clip(polygon[i-1], camera), true),
+                        (transform_world_to_clip(polygon[i], camera), i == n-1),
+                    ], color, fill);
                 }
             } else {
                 for i in 0..polygon.len() {
-                    draw_line_3d(frame, depth, polygon[i], polygon[(i+1) % polygon.len()], camera, color);
+                    draw_line_3d(target, polygon[i], polygon[(i+1) % polygon.len()], camera, color);
                 }
             }
         }
     }
 }
 
-pub fn draw_mesh_3d(frame: &mut [u8], depth: &mut [f64], mesh: &Vec<Vec<DVec3>>, camera: &Camera, color: u32, fill: u32) {
+pub fn draw_mesh_3d(target: &mut RenderTarget, mesh: &Vec<Vec<DVec3>>, camera: &Camera, color: u32, fill: u32) {
     for polygon in mesh {
-        draw_polygon_3d(frame, depth, polygon, camera, color, fill);
+        draw_polygon_3d(target, polygon, camera, color, fill);
     }
 }
 
-// todo: check bounding box
-pub fn draw_object(frame: &mut [u8], depth: &mut [f64], object: &Object, camera: &Camera) {
-    let (scale, _, _) = object.model.to_scale_rotation_translation(); // todo: dedicated lod properties
-    let distance = (object.model.transform_point3(DVec3::ZERO) - camera.model.transform_point3(DVec3::ZERO)).length();
-    if distance > 10000.0 && scale.x < 1000.0 {
-        draw_point_3d(frame, depth, object.model.transform_point3(DVec3::ZERO), camera, object.color);
-    } else if distance > 4000.0 && scale.x < 1000.0 {
-        draw_mesh_3d(frame, depth, &transform_mesh(&object.mesh, object.model), camera, object.color, 0x00000000);
-    } else {
-        draw_mesh_3d(frame, depth, &transform_mesh(&object.mesh, object.model), camera, object.color, object.fill);
+pub fn object_bounds(object: &Object) -> (DVec3, f64) {
+    let bounds = object.mesh.bounds;
+    let center = object.model.transform_point3(bounds.center);
+    let scale = object.model.x_axis.truncate().length()
+        .max(object.model.y_axis.truncate().length())
+        .max(object.model.z_axis.truncate().length());
+    (center, bounds.radius * scale)
+}
+
+pub fn object_lod(target: &RenderTarget, object: &Object, camera: &Camera) -> Lod {
+    let (center, radius) = object_bounds(object);
+    if !sphere_in_frustum(&camera.frustum, center, radius) {
+        return Lod::Skip;
     }
+    let size = projected_size(center, radius, camera, target);
+    object.lods.iter().find(|l| size >= l.min_size).map_or(Lod::Skip, |l| l.lod)
+}
+
+pub fn draw_object(target: &mut RenderTarget, object: &Object, camera: &Camera) -> bool {
+    match object_lod(target, object, camera) {
+        Lod::Mesh => draw_mesh_3d(target, &transform_mesh(&object.mesh.polygons, object.model), camera, object.color, object.fill),
+        Lod::Wireframe => draw_mesh_3d(target, &transform_mesh(&object.mesh.polygons, object.model), camera, object.color, 0x00000000),
+        Lod::Point => draw_point_3d(target, object.model.transform_point3(DVec3::ZERO), camera, object.color),
+        Lod::Skip => return false,
+    }
+    true
 }
 
 pub fn color_to_float(color: u32) -> (f64, f64, f64, f64) {
@@ -363,4 +451,48 @@ pub fn float_to_color((r, g, b, a): (f64, f64, f64, f64)) -> u32 {
     color |= ((b * 255.0) as u32) << 8;
     color |= (a * 255.0) as u32;
     color
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILL: u32 = 0xffffffff;
+
+    fn covered(target: &RenderTarget, x: i32, y: i32) -> bool {
+        target.frame[target.index(x, y)..][..4] != [0; 4]
+    }
+
+    // two triangles sharing a diagonal through pixel centers cover the square exactly once, no gaps or overlap
+    #[test]
+    fn shared_edge_is_covered_once() {
+        let (p0, p1, p2, p3) = (DVec3::new(2.0, 2.0, 0.0), DVec3::new(14.0, 2.0, 0.0), DVec3::new(14.0, 14.0, 0.0), DVec3::new(2.0, 14.0, 0.0));
+        let (mut a, mut b) = (RenderTarget::new(16, 16), RenderTarget::new(16, 16));
+        draw_triangle_fill_outline(&mut a, p0, p1, p2, [false; 3], FILL, FILL);
+        draw_triangle_fill_outline(&mut b, p0, p2, p3, [false; 3], FILL, FILL);
+        for x in 0..16 {
+            for y in 0..16 {
+                let inside = (2..14).contains(&x) && (2..14).contains(&y);
+                assert_eq!(covered(&a, x, y) as u32 + covered(&b, x, y) as u32, inside as u32, "pixel ({x}, {y})");
+            }
+        }
+    }
+
+    // the apex is behind the camera, projecting it without clipping would fold the triangle downwards
+    #[test]
+    fn polygon_behind_near_plane_is_clipped() {
+        let mut target = RenderTarget::new(64, 64);
+        draw_clipped_polygon(&mut target, &[
+            (DVec4::new(-0.5, -0.5, 0.5, 1.0), true),
+            (DVec4::new(0.5, -0.5, 0.5, 1.0), true),
+            (DVec4::new(0.0, 1.0, -3.0, -1.0), true),
+        ], FILL, FILL);
+        // the near plane cuts the sides at y = -0.2 in ndc, where they have spread out to x = +-0.8
+        assert!(covered(&target, 51, 22));
+        assert!(covered(&target, 12, 22));
+        for x in 0..64 {
+            for y in 26..64 {
+                assert!(!covered(&target, x, y), "pixel ({x}, {y})");
+            }
+        }
+    }
+}
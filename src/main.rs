@@ -4,6 +4,7 @@
 
 use enum_map::enum_map;
 use error_iter::ErrorIter as _;
+use glam::DVec3;
 use log::error;
 use pixels::{PixelsBuilder, SurfaceTexture};
 use std::rc::Rc;
@@ -23,8 +24,36 @@ mod game;
 mod sprites;
 mod meshes;
 mod transform;
+mod collision;
+mod weapons;
+mod mining;
+mod orbits;
+mod targeting;
+mod autopilot;
+mod navigation;
+mod galaxy;
+mod save;
+mod controls;
+mod gamepad;
+mod mouse;
+mod replay;
+mod camera;
+#[cfg(not(target_arch = "wasm32"))]
+mod capture;
+#[cfg(not(target_arch = "wasm32"))]
+mod headless;
 
 use game::*;
+use graphics::RenderTarget;
+use autopilot::Autopilot;
+use save::*;
+use controls::*;
+use gamepad::*;
+use mouse::*;
+use replay::*;
+use camera::*;
+#[cfg(not(target_arch = "wasm32"))]
+use capture::*;
 fn main() {
     #[cfg(target_arch = "wasm32")]
     {
@@ -38,6 +67,34 @@ fn main() {
     {
         env_logger::init();
 
+        // exo --check-replay <file>, exits with an error if the recording no longer ends where it did
+        if let Some(name) = get_arg("--check-replay") {
+            match read_file(&name).and_then(|bytes| read_recording(&bytes)).map(check_replay) {
+                Ok(true) => println!("{name}: replay matches"),
+                Ok(false) => {
+                    error!("{name}: replay diverged from the recording");
+                    std::process::exit(1);
+                }
+                Err(err) => {
+                    log_error("read_recording", err);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+
+        // exo --headless <frames> [out_dir] [--seed <seed>]
+        let args: Vec<String> = std::env::args().collect();
+        if let Some(i) = args.iter().position(|a| a == "--headless") {
+            let frames = args.get(i+1).and_then(|s| s.parse().ok()).unwrap_or(1);
+            let out_dir = args.get(i+2).filter(|s| !s.starts_with("--")).map_or("frames", |s| s.as_str());
+            let seed = get_arg("--seed").and_then(|s| s.parse().ok()).unwrap_or(0);
+            if let Err(err) = headless::render_frames(frames, seed, out_dir) {
+                log_error("headless::render_frames", err);
+            }
+            return;
+        }
+
         pollster::block_on(run());
     }
 }
@@ -125,12 +182,60 @@ async fn run() {
 
         builder.build_async().await.expect("Pixels error")
     };
-    let mut game = Game::new();
-
-    let mut depth_buffer: [f64; (WIDTH*HEIGHT) as usize] = [10000.0; (WIDTH*HEIGHT) as usize];
+    let mut game = match get_arg("--seed").and_then(|s| s.parse().ok()) {
+        Some(seed) => Game::with_seed(seed),
+        None => Game::new(),
+    };
+    let mut loaded = false;
+    if let Some(name) = get_arg("--load") {
+        match read_file(&name).and_then(|bytes| load_game(&bytes)) {
+            Ok(save) => (game, loaded) = (save, true),
+            Err(err) => log_error("load_game", err),
+        }
+    }
+    // a loaded game counts its asteroids' time in ticks of the rate it was saved with
+    if let Some(tick_rate) = get_arg("--tick-rate").and_then(|s| s.parse().ok()) {
+        if loaded {
+            error!("--tick-rate can't be combined with --load");
+        } else if valid_tick_rate(tick_rate) {
+            game.tick_rate = tick_rate;
+        } else {
+            error!("--tick-rate must be between {MIN_TICK_RATE} and {MAX_TICK_RATE}");
+        }
+    }
+    // a replay restarts from the recorded seed, recording on top of it continues the replayed flight
+    if let Some(name) = get_arg("--replay") {
+        match read_file(&name).and_then(|bytes| read_recording(&bytes)) {
+            Ok(recording) => game = start_replay(recording),
+            Err(err) => log_error("read_recording", err),
+        }
+    }
+    if let Some(offset) = get_arg("--chase-offset").and_then(|s| parse_offset(&s)) {
+        game.camera_rig.chase_offset = offset;
+    }
+    if let Some(damping) = get_arg("--chase-damping").and_then(|s| s.parse::<f64>().ok()) {
+        game.camera_rig.chase_damping = damping.clamp(0.0, 0.99);
+    }
+    // a loaded game doesn't start from its seed, so it can't be recorded
+    let record_name = get_arg("--record");
+    if record_name.is_some() && loaded {
+        error!("--record can't be combined with --load");
+    } else if record_name.is_some() {
+        game.recording = Some(start_recording(&game));
+    }
+    let mut bindings = load_bindings();
+    let mut rebind = RebindScreen::default();
+    game.key_labels = key_labels(&bindings);
+    let mut gamepad = Gamepad::default();
+    let gamepad_settings = load_gamepad_settings();
+    let mut mouse = MouseFlight::default();
+    let mut target = RenderTarget::new(WIDTH, HEIGHT);
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut recorder: Option<GifRecorder> = None;
 
     let mut t = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
     let mut dt = 0.0;
+    let mut autosave = 0.0;
 
     let res = event_loop.run(|event, elwt| {
         match event {
@@ -138,14 +243,39 @@ async fn run() {
                 event: WindowEvent::RedrawRequested,
                 ..
             } => {
+                dt = (SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64() - t) as f64;
+                t = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
+
                 // Update internal state
-                game.update(dt);
+                game.step(dt);
+
+                // The browser gives no chance to save when the tab closes, so save periodically
+                autosave += dt;
+                if autosave >= AUTOSAVE_INTERVAL {
+                    autosave = 0.0;
+                    if let Err(err) = write_file(AUTOSAVE_NAME, &save_game(&game)) {
+                        log_error("write_file", err);
+                    }
+                }
 
                 // Draw the current frame
-                dt = (SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64() - t) as f64;
-                t = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
+                game.draw(&mut target, dt);
+                draw_mouse_flight(&mut target, &mouse);
+                if rebind.open {
+                    draw_rebind_screen(&mut target, &rebind, &bindings);
+                }
+                pixels.frame_mut().copy_from_slice(&target.frame);
+
+                #[cfg(not(target_arch = "wasm32"))]
+                if recorder.as_mut().is_some_and(|r| r.capture(&target, dt)) {
+                    let recorder = recorder.take().unwrap();
+                    std::thread::spawn(move || {
+                        if let Err(err) = recorder.save(&timestamped_path("capture", "gif")) {
+                            log_error("GifRecorder::save", err);
+                        }
+                    });
+                }
 
-                game.draw(pixels.frame_mut(), &mut depth_buffer, dt);
                 if let Err(err) = pixels.render() {
                     log_error("pixels.render", err);
                     elwt.exit();
@@ -171,73 +301,147 @@ async fn run() {
         }
 
         if input.update(&event) {
-            if input.key_pressed(KeyCode::Escape) || input.close_requested() {
+            gamepad.poll();
+            if input.close_requested() || (input.key_pressed(KeyCode::Escape) && !rebind.open) {
+                save_recording(record_name.as_deref(), &game);
+                if let Err(err) = write_file(AUTOSAVE_NAME, &save_game(&game)) {
+                    log_error("write_file", err);
+                }
                 elwt.exit();
             }
 
-            game.ship.thrust = enum_map! {_ => 0.0};
-            if input.key_held(KeyCode::KeyA) {
-                game.ship.thrust[Thrust::Left] = game.ship.stats.thrust;
-            }
-            if input.key_held(KeyCode::KeyD) {
-                game.ship.thrust[Thrust::Right] = game.ship.stats.thrust;
-            }
-            if input.key_held(KeyCode::KeyR) {
-                game.ship.thrust[Thrust::Up] = game.ship.stats.thrust;
-            }
-            if input.key_held(KeyCode::KeyF) {
-                game.ship.thrust[Thrust::Down] = game.ship.stats.thrust;
-            }
-            if input.key_held(KeyCode::KeyW) {
-                game.ship.thrust[Thrust::Front] = 2.0 * game.ship.stats.thrust;
-            }
-            if input.key_held(KeyCode::KeyS) {
-                game.ship.thrust[Thrust::Back] = game.ship.stats.thrust;
+            game.input.thrust = enum_map! {_ => 0.0};
+            game.input.brake = false;
+            game.input.firing = false;
+            if input.key_pressed(REBIND_KEY) {
+                rebind.open = !rebind.open;
+                rebind.waiting = false;
+            } else if rebind.open {
+                if update_rebind_screen(&mut rebind, &input, &mut bindings) {
+                    game.key_labels = key_labels(&bindings);
+                    if let Err(err) = write_file(CONTROLS_NAME, format_bindings(&bindings).as_bytes()) {
+                        log_error("write_file", err);
+                    }
+                }
+                return;
             }
-            if input.key_held(KeyCode::KeyJ) {
-                game.ship.thrust[Thrust::YawLeft] = game.ship.stats.angular_thrust;
+            let pressed = |action: Action| input.key_pressed(bindings[action]) || gamepad.pressed(action);
+            let held = |action: Action| input.key_held(bindings[action]) || gamepad.held(action);
+
+            if pressed(Action::Save) {
+                if let Err(err) = write_file(SAVE_NAME, &save_game(&game)) {
+                    log_error("write_file", err);
+                }
             }
-            if input.key_held(KeyCode::KeyL) {
-                game.ship.thrust[Thrust::YawRight] = game.ship.stats.angular_thrust;
+            if pressed(Action::Load) {
+                match read_file(SAVE_NAME).and_then(|bytes| load_game(&bytes)) {
+                    Ok(mut loaded) => {
+                        // a loaded game doesn't start from its seed, so the recording ends here
+                        save_recording(record_name.as_deref(), &game);
+                        loaded.camera_rig = std::mem::take(&mut game.camera_rig);
+                        game = loaded;
+                        game.key_labels = key_labels(&bindings);
+                    }
+                    Err(err) => log_error("load_game", err),
+                }
             }
-            if input.key_held(KeyCode::KeyI) {
-                game.ship.thrust[Thrust::PitchDown] = game.ship.stats.angular_thrust;
+
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                if pressed(Action::Screenshot) {
+                    if let Err(err) = save_png(&target, &timestamped_path("screenshot", "png")) {
+                        log_error("save_png", err);
+                    }
+                }
+                if pressed(Action::RecordGif) && recorder.is_none() {
+                    recorder = Some(GifRecorder::new(&target, GIF_DURATION, GIF_SCALE));
+                }
             }
-            if input.key_held(KeyCode::KeyK) {
-                game.ship.thrust[Thrust::PitchUp] = game.ship.stats.angular_thrust;
+
+            for (thrust, value) in &mut game.input.thrust {
+                let strength = match thrust {
+                    Thrust::Front => 2.0 * game.ship.stats.thrust,
+                    Thrust::Left | Thrust::Right | Thrust::Up | Thrust::Down | Thrust::Back => game.ship.stats.thrust,
+                    _ => game.ship.stats.angular_thrust,
+                };
+                if held(thrust_action(thrust)) {
+                    *value = strength;
+                }
             }
-            if input.key_held(KeyCode::KeyU) {
-                game.ship.thrust[Thrust::RollCCW] = game.ship.stats.angular_thrust;
+            apply_gamepad_thrust(&mut game.input, &game.ship.stats, &gamepad, &gamepad_settings);
+            if pressed(Action::MouseFlight) {
+                mouse.mode = mouse.mode.next();
+                mouse.stick = DVec3::ZERO;
+                if let Err(err) = grab_cursor(&window, mouse.mode) {
+                    log_error("grab_cursor", err);
+                }
             }
-            if input.key_held(KeyCode::KeyO) {
-                game.ship.thrust[Thrust::RollCW] = game.ship.stats.angular_thrust;
+            let size = window.inner_size();
+            update_mouse_stick(&mut mouse, &input, size.width as f64, size.height as f64);
+            apply_mouse_thrust(&mut game.input, &game.ship.stats, &mouse);
+            game.input.brake = held(Action::Brake);
+            game.input.boost |= pressed(Action::Boost);
+            game.input.firing = held(Action::Fire);
+            game.input.next_weapon |= pressed(Action::NextWeapon);
+            game.input.cycle_target |= pressed(Action::CycleTarget);
+            game.input.clear_target |= pressed(Action::ClearTarget);
+            for (action, mode) in [
+                (Action::KillRotation, Autopilot::KillRotation),
+                (Action::Prograde, Autopilot::Prograde),
+                (Action::Retrograde, Autopilot::Retrograde),
+                (Action::PointAtTarget, Autopilot::PointAtTarget),
+                (Action::MatchVelocity, Autopilot::MatchVelocity),
+                (Action::Approach, Autopilot::Approach),
+            ] {
+                if pressed(action) {
+                    game.input.autopilot = Some(mode);
+                }
             }
-            if input.key_held(KeyCode::Space) && !game.ship.jumping {
-                game.ship.brake = true;
-            } else {
-                game.ship.brake = false;
+            if pressed(Action::AutopilotOff) {
+                game.input.autopilot = Some(Autopilot::Off);
             }
-            if input.key_pressed(KeyCode::Tab) && !game.ship.jumping && game.ship.boost_cooldown == 0.0 {
-                game.ship.boost = game.ship.stats.boost_strength;
-                game.ship.boost_cooldown = game.ship.stats.boost_cooldown;
+            game.input.cycle_destination |= pressed(Action::CycleDestination);
+            game.input.bookmark |= pressed(Action::Bookmark);
+            game.input.jump |= pressed(Action::Jump);
+            if pressed(Action::CameraMode) {
+                game.camera_rig.mode = game.camera_rig.mode.next();
             }
-            if input.key_pressed(KeyCode::AltLeft) {
-                if !game.ship.jumping {
-                    game.ship.charging_jump = !game.ship.charging_jump;
-                    game.ship.jump_charge = match game.ship.charging_jump {
-                        true => game.ship.stats.jump_charge,
-                        false => 0.0,
-                    }
-                } else {
-                    game.ship.jumping = false;
-                    end_jump(&mut game.ship);
-                }
+            match game.camera_rig.mode {
+                CameraMode::Orbit => update_orbit_input(&mut game.camera_rig, &input, &mouse),
+                CameraMode::Free => take_free_camera_input(&mut game.camera_rig, &mut game.input, &game.ship.stats),
+                _ => (),
             }
         }
     });
     res.unwrap();
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+/// Retrieve the value following a command line flag, e.g. `--tick-rate 120`
+fn get_arg(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter().position(|a| a == name).and_then(|i| args.get(i+1).cloned())
+}
+
+#[cfg(target_arch = "wasm32")]
+/// Retrieve the value of a url query parameter, e.g. `?seed=42` for `--seed`
+fn get_arg(name: &str) -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    let name = name.trim_start_matches('-');
+    search.trim_start_matches('?').split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key == name).then(|| value.to_string())
+    })
+}
+
+fn save_recording(name: Option<&str>, game: &Game) {
+    if let (Some(name), Some(recording)) = (name, &game.recording) {
+        if let Err(err) = write_file(name, &write_recording(recording, &game.ship)) {
+            log_error("write_file", err);
+        }
+    }
+}
+
 fn log_error<E: std::error::Error + 'static>(method_name: &str, err: E) {
     error!("{method_name}() failed: {err}");
     for source in err.sources().skip(1) {
@@ -1,8 +1,82 @@
 #![allow(dead_code)]
 
-use glam::DVec3;
+use glam::{DVec3, DVec4};
 use regex::Regex;
 
+pub struct Mesh {
+	pub polygons: Vec<Vec<DVec3>>,
+	pub bounds: BoundingSphere,
+	pub planes: Vec<DVec4>,
+}
+
+#[derive(Clone, Copy)]
+pub struct BoundingSphere {
+	pub center: DVec3,
+	pub radius: f64,
+}
+
+impl Mesh {
+	pub fn new(polygons: Vec<Vec<DVec3>>) -> Self {
+		let bounds = bounding_sphere(&polygons);
+		let planes = hull_planes(&polygons);
+		Self {
+			polygons,
+			bounds,
+			planes,
+		}
+	}
+}
+
+pub fn bounding_sphere(polygons: &[Vec<DVec3>]) -> BoundingSphere {
+	let mut vertices = polygons.iter().flatten().peekable();
+	if vertices.peek().is_none() {
+		return BoundingSphere { center: DVec3::ZERO, radius: 0.0 };
+	}
+	let (mut min, mut max) = (DVec3::INFINITY, DVec3::NEG_INFINITY);
+	for v in vertices {
+		min = min.min(*v);
+		max = max.max(*v);
+	}
+	let center = (min + max) / 2.0;
+	let radius = polygons.iter().flatten().map(|v| (*v - center).length()).fold(0.0, f64::max);
+	BoundingSphere { center, radius }
+}
+
+// outward facing planes (normal, distance) of the convex hull of the vertices. the face planes alone
+// would cut off every vertex that sits on a concave part of the mesh. brute force over vertex triples,
+// which is fine for the few dozen vertices of the meshes that collide
+pub fn hull_planes(polygons: &[Vec<DVec3>]) -> Vec<DVec4> {
+	let mut vertices: Vec<DVec3> = polygons.iter().flatten().copied().collect();
+	vertices.sort_by(|a, b| a.to_array().partial_cmp(&b.to_array()).unwrap());
+	vertices.dedup();
+	let epsilon = bounding_sphere(polygons).radius * 1e-9;
+	let mut planes: Vec<DVec4> = Vec::new();
+	for i in 0..vertices.len() {
+		for j in i+1..vertices.len() {
+			for k in j+1..vertices.len() {
+				let Some(normal) = (vertices[j] - vertices[i]).cross(vertices[k] - vertices[i]).try_normalize() else {
+					continue;
+				};
+				let distance = normal.dot(vertices[i]);
+				let (mut above, mut below) = (false, false);
+				for v in &vertices {
+					let d = normal.dot(*v) - distance;
+					above |= d > epsilon;
+					below |= d < -epsilon;
+				}
+				if above && below {
+					continue;
+				}
+				let plane = if above {(-normal).extend(-distance)} else {normal.extend(distance)};
+				if !planes.iter().any(|p| p.truncate().distance(plane.truncate()) < 1e-9 && (p.w - plane.w).abs() <= epsilon) {
+					planes.push(plane);
+				}
+			}
+		}
+	}
+	planes
+}
+
 pub fn parse_obj(obj_string: &str) -> Vec<Vec<DVec3>> {
 	let mut vertices = Vec::new();
 	let mut faces = Vec::new();
@@ -141,6 +215,15 @@ pub fn front_thruster_mesh() -> Vec<Vec<DVec3>> {
 	]
 }
 
+pub fn projectile_mesh() -> Vec<Vec<DVec3>> {
+	vec![
+		vec![
+			DVec3::new(0.0, 0.0, 0.0), // tail
+			DVec3::new(0.0, 0.0, -2.0), // tip
+		],
+	]
+}
+
 pub fn asteroid_mesh() -> Vec<Vec<DVec3>> {
 	vec![
 		vec![
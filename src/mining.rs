@@ -0,0 +1,83 @@
+use enum_map::{Enum, EnumMap};
+use glam::DVec3;
+use rand::Rng;
+
+use crate::collision::*;
+use crate::game::*;
+
+// ore per unit of asteroid scale, in tonnes
+pub const ASTEROID_ORE: f64 = 2.0;
+
+#[derive(Enum, Clone, Copy, PartialEq)]
+pub enum Resource {
+    Ice,
+    Iron,
+    Nickel,
+    Platinum,
+}
+
+impl Resource {
+    pub fn name(self) -> &'static str {
+        match self {
+            Resource::Ice => "ICE",
+            Resource::Iron => "IRON",
+            Resource::Nickel => "NICKEL",
+            Resource::Platinum => "PLATINUM",
+        }
+    }
+}
+
+// common resources first, platinum is rare
+pub fn generate_resource(rng: &mut impl Rng) -> Resource {
+    let r = rng.random::<f64>();
+    if r < 0.4 {
+        Resource::Ice
+    } else if r < 0.75 {
+        Resource::Iron
+    } else if r < 0.95 {
+        Resource::Nickel
+    } else {
+        Resource::Platinum
+    }
+}
+
+pub fn cargo_mass(cargo: &EnumMap<Resource, f64>) -> f64 {
+    cargo.values().sum()
+}
+
+// moves ore from the asteroid under the beam into the hold, returns the end of the beam
+pub fn update_mining_beam(ship: &mut Ship, asteroids: &mut [Asteroid], grid: &SpatialHash, muzzle: DVec3, forward: DVec3, dt: f64) -> DVec3 {
+    let range = ship.stats.mining_range;
+    let Some((i, t)) = raycast_asteroids(muzzle, forward, range, asteroids, grid) else {
+        ship.mining = None;
+        return muzzle + forward * range;
+    };
+    let asteroid = &mut asteroids[i];
+    let space = ship.stats.cargo_capacity - cargo_mass(&ship.cargo);
+    let amount = f64::max(0.0, f64::min(ship.stats.mining_rate * dt, f64::min(asteroid.ore, space)));
+    asteroid.ore -= amount;
+    asteroid.generated = None;
+    ship.cargo[asteroid.resource] += amount;
+    ship.mining = Some((asteroid.resource, asteroid.ore));
+    muzzle + forward * t
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::collision::tests::{orbiting_rock, outlying_point};
+
+    use super::*;
+
+    // the beam ends on the rock as soon as the tip of an outlying vertex is in range
+    #[test]
+    fn beam_reaches_outlying_vertex() {
+        let (mut game, i) = orbiting_rock();
+        let (point, outward) = outlying_point(&game.asteroids[i].object);
+        let muzzle = point + outward * (game.ship.stats.mining_range - 1.0);
+        let ore = game.asteroids[i].ore;
+
+        update_mining_beam(&mut game.ship, &mut game.asteroids, &game.collision_grid, muzzle, -outward, 1.0);
+        assert!(game.ship.mining.is_some(), "beam fell short");
+        assert!(game.asteroids[i].ore < ore);
+    }
+}
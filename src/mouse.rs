@@ -0,0 +1,100 @@
+use glam::DVec3;
+use winit::error::ExternalError;
+use winit::window::{CursorGrabMode, Window};
+use winit_input_helper::WinitInputHelper;
+
+use crate::game::*;
+use crate::gamepad::*;
+use crate::graphics::*;
+use crate::sprites::*;
+
+// the cursor reaches full deflection this fraction of half the window height away from the center
+pub const MOUSE_CURSOR_RANGE: f64 = 0.5;
+// with pointer lock the stick moves this many pixels of relative motion from center to full deflection
+pub const MOUSE_LOCKED_RANGE: f64 = 300.0;
+pub const MOUSE_DEADZONE: f64 = 0.05;
+
+pub const STICK_SIZE: f64 = 12.0;
+pub const STICK_COLOR: u32 = 0x808080ff;
+
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum MouseMode {
+    #[default]
+    Off,
+    Cursor,
+    Locked,
+}
+
+// virtual stick in screen orientation, each axis -1..1, steering yaw and pitch
+#[derive(Default)]
+pub struct MouseFlight {
+    pub mode: MouseMode,
+    pub stick: DVec3,
+}
+
+impl MouseMode {
+    pub fn next(self) -> MouseMode {
+        match self {
+            MouseMode::Off => MouseMode::Cursor,
+            MouseMode::Cursor => MouseMode::Locked,
+            MouseMode::Locked => MouseMode::Off,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MouseMode::Off => "",
+            MouseMode::Cursor => "MOUSE",
+            MouseMode::Locked => "MOUSE LOCK",
+        }
+    }
+}
+
+// pointer lock isn't available everywhere, confining the hidden cursor to the window still gives relative motion
+pub fn grab_cursor(window: &Window, mode: MouseMode) -> Result<(), ExternalError> {
+    window.set_cursor_visible(mode != MouseMode::Locked);
+    match mode {
+        MouseMode::Locked => window.set_cursor_grab(CursorGrabMode::Locked).or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined)),
+        _ => window.set_cursor_grab(CursorGrabMode::None),
+    }
+}
+
+// cursor mode follows the cursor's offset from the window center, locked mode accumulates relative motion
+// and the stick stays where it was left. width and height are the window's physical size
+pub fn update_mouse_stick(mouse: &mut MouseFlight, input: &WinitInputHelper, width: f64, height: f64) {
+    match mouse.mode {
+        MouseMode::Off => mouse.stick = DVec3::ZERO,
+        MouseMode::Cursor => {
+            if let Some((x, y)) = input.cursor() {
+                let range = height / 2.0 * MOUSE_CURSOR_RANGE;
+                mouse.stick = DVec3::new(x as f64 - width / 2.0, height / 2.0 - y as f64, 0.0) / range;
+            }
+        }
+        MouseMode::Locked => {
+            let (dx, dy) = input.mouse_diff();
+            mouse.stick += DVec3::new(dx as f64, -dy as f64, 0.0) / MOUSE_LOCKED_RANGE;
+        }
+    }
+    mouse.stick = mouse.stick.clamp(DVec3::new(-1.0, -1.0, 0.0), DVec3::new(1.0, 1.0, 0.0));
+}
+
+// stick up pitches the nose up, keys and gamepad already set on a channel win if they are stronger
+pub fn apply_mouse_thrust(input: &mut PlayerInput, stats: &ShipStats, mouse: &MouseFlight) {
+    if mouse.mode == MouseMode::Off {
+        return;
+    }
+    let axis = |value: f64| ((value.abs() - MOUSE_DEADZONE) / (1.0 - MOUSE_DEADZONE)).max(0.0) * value.signum();
+    let angular = stats.angular_thrust;
+    add_thrust_axis(input, Thrust::YawRight, Thrust::YawLeft, axis(mouse.stick.x), angular, angular);
+    add_thrust_axis(input, Thrust::PitchUp, Thrust::PitchDown, axis(mouse.stick.y), angular, angular);
+}
+
+pub fn draw_mouse_flight(target: &mut RenderTarget, mouse: &MouseFlight) {
+    if mouse.mode == MouseMode::Off {
+        return;
+    }
+    let center = DVec3::new((target.width / 2) as f64, (target.height / 2) as f64, 0.0);
+    draw_virtual_stick(target, center, STICK_SIZE, mouse.stick, STICK_COLOR);
+    let name = mouse.mode.name();
+    draw_text(target, center - DVec3::new((name.len() * 6) as f64 / 2.0, STICK_SIZE + 8.0, 0.0), name, FONT_5PX, 6, 1, STICK_COLOR);
+}
@@ -0,0 +1,143 @@
+use glam::DVec3;
+
+use crate::autopilot::*;
+use crate::galaxy::*;
+use crate::game::*;
+use crate::orbits::*;
+use crate::transform::FAR;
+
+// drop-out distances from the destination point
+pub const PLANET_ALTITUDE: f64 = 10000.0;
+pub const RING_DISTANCE: f64 = 2000.0;
+pub const BOOKMARK_DISTANCE: f64 = 100.0;
+
+// the jump only starts once the nose is within this angle of the destination
+pub const JUMP_ALIGNMENT: f64 = 0.01;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Destination {
+    Planet,
+    Ring,
+    Bookmark(usize),
+    System(usize),
+}
+
+// where the jump drops out and the velocity the ship is left with
+pub struct Waypoint {
+    pub position: DVec3,
+    pub velocity: DVec3,
+}
+
+// bookmarks only show up as destinations in the system they were placed in
+pub struct Bookmark {
+    pub system: usize,
+    pub position: DVec3,
+}
+
+impl Destination {
+    pub fn name(self, galaxy: &[StarSystem]) -> String {
+        match self {
+            Destination::Planet => "PLANET".to_string(),
+            Destination::Ring => "RING".to_string(),
+            Destination::Bookmark(i) => format!("BM{}", i + 1),
+            Destination::System(i) => galaxy[i].name.clone(),
+        }
+    }
+}
+
+// planet, ring if there is one, this system's bookmarks, then the other systems
+pub fn available_destinations(planet: &Planet, bookmarks: &[Bookmark], galaxy: &[StarSystem], system: usize) -> Vec<Destination> {
+    let mut destinations = vec![Destination::Planet];
+    if planet.ring.is_some() {
+        destinations.push(Destination::Ring);
+    }
+    destinations.extend((0..bookmarks.len()).filter(|&i| bookmarks[i].system == system).map(Destination::Bookmark));
+    destinations.extend((0..galaxy.len()).filter(|&i| i != system).map(Destination::System));
+    destinations
+}
+
+// steps through the available destinations, going back to none after the last one
+pub fn cycle_destination(current: Option<Destination>, destinations: &[Destination]) -> Option<Destination> {
+    match current.and_then(|c| destinations.iter().position(|&d| d == c)) {
+        Some(i) => destinations.get(i + 1).copied(),
+        None => destinations.first().copied(),
+    }
+}
+
+// planet and ring arrivals are placed into a circular orbit, bookmarks are arrived at standing still,
+// other systems are never reached in space, the transit ends the jump after a fixed time instead
+pub fn destination_waypoint(destination: Destination, position: DVec3, planet: &Planet, bookmarks: &[Bookmark], galaxy: &[StarSystem], system: usize) -> Waypoint {
+    let (point, distance) = match destination {
+        Destination::Planet => (planet.position, planet.radius + PLANET_ALTITUDE),
+        Destination::Ring => {
+            let offset = position - planet.position;
+            let in_plane = (offset - planet.axis * offset.dot(planet.axis)).try_normalize().unwrap_or(planet.axis.any_orthonormal_vector());
+            let (inner, outer) = planet.ring.unwrap_or((planet.radius, planet.radius));
+            (planet.position + in_plane * (inner + outer) / 2.0, RING_DISTANCE)
+        }
+        Destination::Bookmark(i) => (bookmarks[i].position, BOOKMARK_DISTANCE),
+        Destination::System(i) => (position + system_direction(galaxy, system, i) * FAR, 0.0),
+    };
+    let position = point - (point - position).normalize_or_zero() * distance;
+    let velocity = match destination {
+        Destination::Planet | Destination::Ring => circular_velocity(planet, position, planet.axis),
+        Destination::Bookmark(_) | Destination::System(_) => DVec3::ZERO,
+    };
+    Waypoint { position, velocity }
+}
+
+// aligns the ship while charging, holds the charge until aligned, then steers the jump and drops out on arrival.
+// without a destination the jump goes wherever the nose points
+pub fn update_navigation(ship: &mut Ship, waypoint: Option<Waypoint>, dt: f64) {
+    ship.jump_aligned = true;
+    let Some(waypoint) = waypoint else {
+        return;
+    };
+    let offset = waypoint.position - ship.position;
+
+    if ship.charging_jump {
+        point_towards(ship, offset);
+        let forward = ship.rotation * DVec3::new(0.0, 0.0, -1.0);
+        ship.jump_aligned = forward.angle_between(offset) <= JUMP_ALIGNMENT;
+    }
+
+    if ship.jumping {
+        if offset.length() <= ship.stats.jump_speed * dt {
+            ship.jumping = false;
+            end_jump(ship);
+            ship.position = waypoint.position;
+            ship.velocity = waypoint.velocity;
+        } else {
+            ship.velocity = offset.normalize() * ship.stats.jump_speed;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the charge alone would run out after 3 s, turning around takes longer than that
+    #[test]
+    fn jump_waits_for_alignment() {
+        let mut game = Game::with_seed(1);
+        let ship = &mut game.ship;
+        let dt = 1.0 / 60.0;
+        let destination = ship.position + ship.rotation * DVec3::new(0.0, 0.0, 100000.0);
+        ship.charging_jump = true;
+        ship.jump_charge = ship.stats.jump_charge;
+
+        for tick in 0..6000 {
+            update_navigation(ship, Some(Waypoint { position: destination, velocity: DVec3::ZERO }), dt);
+            let forward = ship.rotation * DVec3::new(0.0, 0.0, -1.0);
+            let angle = forward.angle_between(destination - ship.position);
+            update_ship_movement(ship, dt);
+            if ship.jumping {
+                assert!(angle <= JUMP_ALIGNMENT, "jumped {angle} rad off the destination at tick {tick}");
+                assert!(tick as f64 * dt > ship.stats.jump_charge);
+                return;
+            }
+        }
+        panic!("never jumped");
+    }
+}
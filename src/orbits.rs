@@ -0,0 +1,168 @@
+use std::f64::consts::TAU;
+
+use glam::{DMat4, DQuat, DVec3};
+
+use crate::collision::*;
+use crate::game::*;
+
+pub const GRAVITATIONAL_CONSTANT: f64 = 6.674e-11;
+
+// roughly 10 m/s^2 at the surface of a planet of the reference scale, mass grows with the surface area
+// so every generated planet has about the same surface gravity
+pub const PLANET_MASS: f64 = 1.35e20;
+pub const PLANET_SCALE: f64 = 30000.0;
+
+pub const PREDICTION_STEPS: usize = 400;
+pub const PREDICTION_DT: f64 = 6.0;
+
+pub const KEPLER_ITERATIONS: usize = 50;
+
+pub struct Planet {
+    pub position: DVec3,
+    pub mass: f64,
+    pub radius: f64,
+    pub axis: DVec3,
+    pub ring: Option<(f64, f64)>,
+}
+
+// elliptic orbit around the planet, the eccentric anomaly is kept to warm start kepler's equation
+#[derive(Clone, Copy)]
+pub struct Orbit {
+    pub semi_major_axis: f64,
+    pub eccentricity: f64,
+    pub periapsis: DVec3,
+    pub normal: DVec3,
+    pub mean_motion: f64,
+    pub mean_anomaly: f64,
+    pub eccentric_anomaly: f64,
+}
+
+impl Planet {
+    pub fn mu(&self) -> f64 {
+        GRAVITATIONAL_CONSTANT * self.mass
+    }
+}
+
+pub fn gravity(planet: &Planet, position: DVec3) -> DVec3 {
+    let offset = planet.position - position;
+    let distance = f64::max(offset.length(), planet.radius);
+    offset / distance * planet.mu() / (distance * distance)
+}
+
+// velocity of a prograde circular orbit around the given axis
+pub fn circular_velocity(planet: &Planet, position: DVec3, normal: DVec3) -> DVec3 {
+    let offset = position - planet.position;
+    normal.cross(offset).normalize_or_zero() * f64::sqrt(planet.mu() / offset.length())
+}
+
+// circular orbit through the ship's position, nose pointing prograde
+pub fn spawn_in_orbit(ship: &mut Ship, planet: &Planet) {
+    ship.velocity = circular_velocity(planet, ship.position, planet.axis);
+    ship.rotation = DQuat::look_to_rh(ship.velocity.normalize(), planet.axis).inverse();
+    ship.hull.model = DMat4::from_rotation_translation(ship.rotation, ship.position);
+}
+
+// returns none for unbound trajectories
+pub fn orbit_from_state(mu: f64, position: DVec3, velocity: DVec3) -> Option<Orbit> {
+    let r = position.length();
+    let h = position.cross(velocity);
+    let normal = h.try_normalize()?;
+    let eccentricity_vector = velocity.cross(h) / mu - position / r;
+    let eccentricity = eccentricity_vector.length();
+    let energy = velocity.length_squared() / 2.0 - mu / r;
+    if energy >= 0.0 || eccentricity >= 1.0 {
+        return None;
+    }
+
+    let semi_major_axis = -mu / (2.0 * energy);
+    let periapsis = if eccentricity > 1e-9 {eccentricity_vector / eccentricity} else {position / r};
+    let q = normal.cross(periapsis);
+    let true_anomaly = f64::atan2(position.dot(q), position.dot(periapsis));
+    let eccentric_anomaly = 2.0 * f64::atan2(f64::sqrt(1.0 - eccentricity) * (true_anomaly / 2.0).sin(), f64::sqrt(1.0 + eccentricity) * (true_anomaly / 2.0).cos());
+    Some(Orbit {
+        semi_major_axis,
+        eccentricity,
+        periapsis,
+        normal,
+        mean_motion: f64::sqrt(mu / semi_major_axis.powi(3)),
+        mean_anomaly: eccentric_anomaly - eccentricity * eccentric_anomaly.sin(),
+        eccentric_anomaly,
+    })
+}
+
+// position relative to the planet
+pub fn orbit_position(orbit: &Orbit) -> DVec3 {
+    let (sin, cos) = orbit.eccentric_anomaly.sin_cos();
+    anomaly_position(orbit, sin, cos)
+}
+
+fn anomaly_position(orbit: &Orbit, sin: f64, cos: f64) -> DVec3 {
+    let (a, e) = (orbit.semi_major_axis, orbit.eccentricity);
+    let q = orbit.normal.cross(orbit.periapsis);
+    orbit.periapsis * a * (cos - e) + q * a * f64::sqrt(1.0 - e * e) * sin
+}
+
+pub fn orbit_velocity(orbit: &Orbit, mu: f64) -> DVec3 {
+    let (sin, cos) = orbit.eccentric_anomaly.sin_cos();
+    let (a, e) = (orbit.semi_major_axis, orbit.eccentricity);
+    let q = orbit.normal.cross(orbit.periapsis);
+    let r = a * (1.0 - e * cos);
+    (orbit.periapsis * -sin + q * f64::sqrt(1.0 - e * e) * cos) * f64::sqrt(mu * a) / r
+}
+
+// the anomaly barely changes per tick, so a single newton step from the last solution is enough and
+// the sine and cosine of the new anomaly can be extrapolated from the old ones, returns the new position
+pub fn advance_orbit(orbit: &mut Orbit, dt: f64) -> DVec3 {
+    let e = orbit.eccentricity;
+    let (sin, cos) = orbit.eccentric_anomaly.sin_cos();
+    orbit.mean_anomaly += orbit.mean_motion * dt;
+    let step = (orbit.mean_anomaly - orbit.eccentric_anomaly + e * sin) / (1.0 - e * cos);
+    orbit.eccentric_anomaly += step;
+    anomaly_position(orbit, sin + cos * step, cos - sin * step)
+}
+
+// jumps ahead by any amount of time, unlike advance_orbit the anomaly is solved to convergence
+pub fn propagate_orbit(orbit: &mut Orbit, time: f64) -> DVec3 {
+    let e = orbit.eccentricity;
+    orbit.mean_anomaly += orbit.mean_motion * time;
+    orbit.eccentric_anomaly += orbit.mean_motion * time;
+    for _ in 0..KEPLER_ITERATIONS {
+        let (sin, cos) = orbit.eccentric_anomaly.sin_cos();
+        let step = (orbit.mean_anomaly - orbit.eccentric_anomaly + e * sin) / (1.0 - e * cos);
+        orbit.eccentric_anomaly += step;
+        if step.abs() < 1e-12 {
+            break;
+        }
+    }
+    let (sin, cos) = orbit.eccentric_anomaly.sin_cos();
+    anomaly_position(orbit, sin, cos)
+}
+
+pub fn update_asteroid_orbits(asteroids: &mut [Asteroid], planet: &Planet, grid: &mut SpatialHash, dt: f64) {
+    for asteroid in asteroids.iter_mut() {
+        if let Some(orbit) = &mut asteroid.orbit {
+            asteroid.object.model.w_axis = (planet.position + advance_orbit(orbit, dt)).extend(1.0);
+        }
+    }
+    refresh_collision_grid(grid, asteroids, dt);
+}
+
+// integrates the ballistic trajectory ahead for up to one revolution or until it hits the planet
+pub fn predict_trajectory(planet: &Planet, position: DVec3, velocity: DVec3) -> Vec<DVec3> {
+    let (mut position, mut velocity) = (position, velocity);
+    let mut points = vec![position];
+    let mut angle = 0.0;
+    for _ in 0..PREDICTION_STEPS {
+        let previous = position - planet.position;
+        velocity += gravity(planet, position) * PREDICTION_DT / 2.0;
+        position += velocity * PREDICTION_DT;
+        velocity += gravity(planet, position) * PREDICTION_DT / 2.0;
+        points.push(position);
+
+        angle += previous.angle_between(position - planet.position);
+        if angle >= TAU || (position - planet.position).length() < planet.radius {
+            break;
+        }
+    }
+    points
+}
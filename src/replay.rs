@@ -0,0 +1,253 @@
+use std::io::Result;
+
+use enum_map::Enum;
+use glam::DVec3;
+
+use crate::game::*;
+use crate::save::*;
+
+pub const REPLAY_MAGIC: &[u8; 4] = b"EXOR";
+pub const REPLAY_VERSION: u32 = 1;
+
+const BRAKE: u16 = 1;
+const BOOST: u16 = 2;
+const JUMP: u16 = 4;
+const FIRING: u16 = 8;
+const NEXT_WEAPON: u16 = 16;
+const CYCLE_TARGET: u16 = 32;
+const CLEAR_TARGET: u16 = 64;
+const CYCLE_DESTINATION: u16 = 128;
+const BOOKMARK: u16 = 256;
+// followed by the autopilot mode
+const AUTOPILOT: u16 = 512;
+
+// every tick's input since the game was generated from the seed, runs of identical ticks are stored once.
+// the ship's final position and velocity are kept to check that a replay still ends in the same place
+pub struct Recording {
+    pub seed: u64,
+    pub tick_rate: f64,
+    pub runs: Vec<(u32, PlayerInput)>,
+    pub end: Option<(DVec3, DVec3)>,
+}
+
+// position in the recording, as the run index and the ticks already played from that run
+pub struct Replay {
+    pub recording: Recording,
+    pub run: usize,
+    pub tick: u32,
+}
+
+// only valid for a game that hasn't been updated since it was created from its seed
+pub fn start_recording(game: &Game) -> Recording {
+    Recording {
+        seed: game.seed,
+        tick_rate: game.tick_rate,
+        runs: Vec::new(),
+        end: None,
+    }
+}
+
+pub fn record_input(recording: &mut Recording, input: PlayerInput) {
+    match recording.runs.last_mut() {
+        Some((count, last)) if *last == input && *count < u32::MAX => *count += 1,
+        _ => recording.runs.push((1, input)),
+    }
+}
+
+pub fn recording_ticks(recording: &Recording) -> u64 {
+    recording.runs.iter().map(|&(count, _)| count as u64).sum()
+}
+
+pub fn start_replay(recording: Recording) -> Game {
+    let mut game = Game::with_seed(recording.seed);
+    game.tick_rate = recording.tick_rate;
+    game.replay = Some(Replay { recording, run: 0, tick: 0 });
+    game
+}
+
+pub fn next_replay_input(replay: &mut Replay) -> Option<PlayerInput> {
+    let &(count, input) = replay.recording.runs.get(replay.run)?;
+    replay.tick += 1;
+    if replay.tick == count {
+        replay.run += 1;
+        replay.tick = 0;
+    }
+    Some(input)
+}
+
+// plays the whole recording through update without drawing. the simulation is deterministic for the same
+// inputs, so anything but a bit exact match with the recorded end state is a regression
+pub fn check_replay(recording: Recording) -> bool {
+    let ticks = recording_ticks(&recording);
+    let end = recording.end;
+    let mut game = start_replay(recording);
+    let dt = 1.0 / game.tick_rate;
+    for _ in 0..ticks {
+        game.update(dt);
+    }
+    end == Some((game.ship.position, game.ship.velocity))
+}
+
+// per run the tick count, the flags, a mask of the thrust channels in use and their values
+pub fn write_recording(recording: &Recording, ship: &Ship) -> Vec<u8> {
+    let mut w = SaveWriter::default();
+    w.bytes.extend_from_slice(REPLAY_MAGIC);
+    w.u32(REPLAY_VERSION);
+    w.u64(recording.seed);
+    w.f64(recording.tick_rate);
+    w.vec3(ship.position);
+    w.vec3(ship.velocity);
+
+    w.u64(recording.runs.len() as u64);
+    for (count, input) in &recording.runs {
+        w.u32(*count);
+        let flags = [
+            (input.brake, BRAKE),
+            (input.boost, BOOST),
+            (input.jump, JUMP),
+            (input.firing, FIRING),
+            (input.next_weapon, NEXT_WEAPON),
+            (input.cycle_target, CYCLE_TARGET),
+            (input.clear_target, CLEAR_TARGET),
+            (input.cycle_destination, CYCLE_DESTINATION),
+            (input.bookmark, BOOKMARK),
+            (input.autopilot.is_some(), AUTOPILOT),
+        ];
+        w.u16(flags.iter().filter(|(set, _)| *set).map(|(_, flag)| flag).sum());
+        if let Some(mode) = input.autopilot {
+            w.variant(mode);
+        }
+        let mask = input.thrust.iter().filter(|(_, t)| **t != 0.0).map(|(thrust, _)| 1 << thrust.into_usize()).sum();
+        w.u16(mask);
+        input.thrust.values().filter(|t| **t != 0.0).for_each(|&t| w.f64(t));
+    }
+    w.bytes
+}
+
+pub fn read_recording(bytes: &[u8]) -> Result<Recording> {
+    let mut r = SaveReader::new(bytes);
+    if r.take::<4>()? != *REPLAY_MAGIC {
+        return Err(invalid_data("not a recording"));
+    }
+    if r.u32()? != REPLAY_VERSION {
+        return Err(invalid_data("unsupported recording version"));
+    }
+    let seed = r.u64()?;
+    let tick_rate = r.f64()?;
+    if !valid_tick_rate(tick_rate) {
+        return Err(invalid_data("corrupt tick rate"));
+    }
+    let end = Some((r.vec3()?, r.vec3()?));
+
+    let count = r.u64()? as usize;
+    let mut runs = Vec::with_capacity(usize::min(count, r.bytes.len()));
+    for _ in 0..count {
+        let ticks = r.u32()?;
+        let flags = r.u16()?;
+        let autopilot = if flags & AUTOPILOT != 0 {Some(r.variant()?)} else {None};
+        let mask = r.u16()?;
+        if ticks == 0 || flags >= AUTOPILOT << 1 || mask >> Thrust::LENGTH != 0 {
+            return Err(invalid_data("corrupt recording"));
+        }
+        let mut input = PlayerInput {
+            brake: flags & BRAKE != 0,
+            boost: flags & BOOST != 0,
+            jump: flags & JUMP != 0,
+            firing: flags & FIRING != 0,
+            next_weapon: flags & NEXT_WEAPON != 0,
+            cycle_target: flags & CYCLE_TARGET != 0,
+            clear_target: flags & CLEAR_TARGET != 0,
+            cycle_destination: flags & CYCLE_DESTINATION != 0,
+            bookmark: flags & BOOKMARK != 0,
+            autopilot,
+            ..Default::default()
+        };
+        for (thrust, value) in &mut input.thrust {
+            if mask & (1 << thrust.into_usize()) != 0 {
+                *value = r.f64()?;
+            }
+        }
+        runs.push((ticks, input));
+    }
+    Ok(Recording { seed, tick_rate, runs, end })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::autopilot::Autopilot;
+    use crate::weapons::Weapon;
+
+    use super::*;
+
+    const TICKS: u32 = 650;
+
+    // a flight using every kind of input, including the commands that don't move the ship themselves
+    fn scripted_input(tick: u32) -> PlayerInput {
+        let mut input = PlayerInput::default();
+        match tick {
+            0..=59 => input.thrust[Thrust::Front] = 10.0,
+            100..=129 => input.thrust[Thrust::YawLeft] = 2.0,
+            150..=199 => input.firing = true,
+            300..=329 => input.brake = true,
+            _ => (),
+        }
+        input.boost = tick == 10;
+        input.next_weapon = tick == 125 || tick == 140;
+        input.cycle_target = tick == 50 || tick == 250;
+        input.autopilot = match tick {
+            55 => Some(Autopilot::PointAtTarget),
+            350 => Some(Autopilot::KillRotation),
+            450 => Some(Autopilot::Off),
+            _ => None,
+        };
+        input.clear_target = tick == 400;
+        input.bookmark = tick == 500;
+        input.cycle_destination = tick == 550 || tick == 551;
+        input.jump = tick == 580;
+        input
+    }
+
+    #[test]
+    fn replay_matches_recording() {
+        let mut game = Game::with_seed(42);
+        game.recording = Some(start_recording(&game));
+        let dt = 1.0 / game.tick_rate;
+        let (mut locked, mut piloted, mut navigated) = (false, false, false);
+        for tick in 0..TICKS {
+            game.input = scripted_input(tick);
+            game.update(dt);
+            locked |= game.target_lock.is_some();
+            piloted |= game.ship.autopilot != Autopilot::Off;
+            navigated |= game.destination.is_some();
+        }
+        // the commands have to have done something for the comparison to mean anything
+        assert!(locked && piloted && navigated);
+        assert!(game.ship.weapon == Weapon::Miner && game.bookmarks.len() == 1);
+        let bytes = write_recording(game.recording.as_ref().unwrap(), &game.ship);
+
+        let mut replayed = start_replay(read_recording(&bytes).unwrap());
+        for _ in 0..TICKS {
+            replayed.update(dt);
+        }
+        let (ship, other) = (&game.ship, &replayed.ship);
+        assert!(ship.position == other.position && ship.velocity == other.velocity);
+        assert!(ship.rotation == other.rotation && ship.angular_velocity == other.angular_velocity);
+        assert!(ship.weapon == other.weapon && ship.autopilot == other.autopilot && ship.jumping == other.jumping);
+        assert!(ship.integrity == other.integrity && ship.cargo == other.cargo);
+        assert_eq!(game.target_lock, replayed.target_lock);
+        assert!(game.destination == replayed.destination && game.system == replayed.system);
+        assert!(game.bookmarks.iter().zip(&replayed.bookmarks).all(|(a, b)| a.position == b.position));
+    }
+
+    // the rate sits after the magic, version and seed
+    #[test]
+    fn corrupt_tick_rate_is_rejected() {
+        let game = Game::with_seed(1);
+        let mut bytes = write_recording(&start_recording(&game), &game.ship);
+        assert!(read_recording(&bytes).is_ok());
+        for tick_rate in [0.0, -60.0, f64::NAN, f64::INFINITY] {
+            bytes[16..24].copy_from_slice(&tick_rate.to_le_bytes());
+            assert!(read_recording(&bytes).is_err());
+        }
+    }
+}
@@ -0,0 +1,760 @@
+use std::io::{Error, ErrorKind, Result};
+use std::rc::Rc;
+
+use enum_map::Enum;
+use glam::{DMat3, DMat4, DQuat, DVec3, DVec4};
+use log::warn;
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+use crate::collision::*;
+use crate::galaxy::*;
+use crate::game::*;
+use crate::meshes::*;
+use crate::mining::*;
+use crate::navigation::*;
+use crate::orbits::*;
+use crate::weapons::*;
+
+pub const SAVE_MAGIC: &[u8; 4] = b"EXO\0";
+pub const SAVE_VERSION: u32 = 3;
+
+// file name on native, local storage key on wasm, see write_file. the save and load actions use SAVE_NAME,
+// autosaves and the save on exit go to their own slot so they never overwrite it, --load resumes from either
+pub const SAVE_NAME: &str = "exo.sav";
+pub const AUTOSAVE_NAME: &str = "exo-auto.sav";
+pub const AUTOSAVE_INTERVAL: f64 = 60.0;
+
+// little endian binary, fields are written in declaration order
+#[derive(Default)]
+pub struct SaveWriter {
+    pub bytes: Vec<u8>,
+}
+
+pub struct SaveReader<'a> {
+    pub bytes: &'a [u8],
+    pub offset: usize,
+}
+
+impl SaveWriter {
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u128(&mut self, value: u128) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn f64(&mut self, value: f64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn variant<T: Enum>(&mut self, value: T) {
+        self.u8(value.into_usize() as u8);
+    }
+
+    pub fn vec3(&mut self, value: DVec3) {
+        for v in value.to_array() {
+            self.f64(v);
+        }
+    }
+
+    pub fn vec4(&mut self, value: DVec4) {
+        for v in value.to_array() {
+            self.f64(v);
+        }
+    }
+
+    pub fn quat(&mut self, value: DQuat) {
+        for v in value.to_array() {
+            self.f64(v);
+        }
+    }
+
+    pub fn mat3(&mut self, value: DMat3) {
+        for v in value.to_cols_array() {
+            self.f64(v);
+        }
+    }
+
+    pub fn mat4(&mut self, value: DMat4) {
+        for v in value.to_cols_array() {
+            self.f64(v);
+        }
+    }
+
+    // object models are affine, the last row is left out
+    pub fn affine(&mut self, value: DMat4) {
+        for axis in [value.x_axis, value.y_axis, value.z_axis, value.w_axis] {
+            self.vec3(axis.truncate());
+        }
+    }
+
+    pub fn pose(&mut self, value: Pose) {
+        self.vec3(value.position);
+        self.quat(value.rotation);
+    }
+}
+
+impl<'a> SaveReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, offset: 0 }
+    }
+
+    pub fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let bytes = self.bytes.get(self.offset..self.offset + N).ok_or(Error::new(ErrorKind::UnexpectedEof, "save data ends early"))?;
+        self.offset += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    pub fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    pub fn u128(&mut self) -> Result<u128> {
+        Ok(u128::from_le_bytes(self.take()?))
+    }
+
+    pub fn f64(&mut self) -> Result<f64> {
+        Ok(f64::from_le_bytes(self.take()?))
+    }
+
+    pub fn bool(&mut self) -> Result<bool> {
+        Ok(self.u8()? != 0)
+    }
+
+    // checked against the variant count so a corrupt byte can't panic inside enum_map
+    pub fn variant<T: Enum>(&mut self) -> Result<T> {
+        let index = self.u8()? as usize;
+        if index >= T::LENGTH {
+            return Err(invalid_data("unknown enum variant"));
+        }
+        Ok(T::from_usize(index))
+    }
+
+    // indices are checked against the length of the list they point into
+    pub fn index(&mut self, len: usize) -> Result<usize> {
+        let index = self.u64()? as usize;
+        if index >= len {
+            return Err(invalid_data("index out of range"));
+        }
+        Ok(index)
+    }
+
+    pub fn vec3(&mut self) -> Result<DVec3> {
+        Ok(DVec3::new(self.f64()?, self.f64()?, self.f64()?))
+    }
+
+    pub fn vec4(&mut self) -> Result<DVec4> {
+        Ok(DVec4::new(self.f64()?, self.f64()?, self.f64()?, self.f64()?))
+    }
+
+    pub fn quat(&mut self) -> Result<DQuat> {
+        Ok(DQuat::from_xyzw(self.f64()?, self.f64()?, self.f64()?, self.f64()?))
+    }
+
+    pub fn mat3(&mut self) -> Result<DMat3> {
+        Ok(DMat3::from_cols(self.vec3()?, self.vec3()?, self.vec3()?))
+    }
+
+    pub fn mat4(&mut self) -> Result<DMat4> {
+        Ok(DMat4::from_cols(self.vec4()?, self.vec4()?, self.vec4()?, self.vec4()?))
+    }
+
+    pub fn affine(&mut self) -> Result<DMat4> {
+        Ok(DMat4::from_cols(self.vec3()?.extend(0.0), self.vec3()?.extend(0.0), self.vec3()?.extend(0.0), self.vec3()?.extend(1.0)))
+    }
+
+    pub fn pose(&mut self) -> Result<Pose> {
+        Ok(Pose { position: self.vec3()?, rotation: self.quat()? })
+    }
+}
+
+pub fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+// the rng is saved as its seed, stream and position in the stream, so the restored session continues with the
+// same numbers and the running one isn't touched. meshes, stars and the planet are regenerated from the seeds,
+// so are the asteroids nothing has touched yet. those are stored as runs of generated indices in list order
+pub fn save_game(game: &Game) -> Vec<u8> {
+    let mut w = SaveWriter::default();
+    w.bytes.extend_from_slice(SAVE_MAGIC);
+    w.u32(SAVE_VERSION);
+
+    w.u64(game.seed);
+    w.bytes.extend_from_slice(&game.rng.get_seed());
+    w.u64(game.rng.get_stream());
+    w.u128(game.rng.get_word_pos());
+    w.u64(game.system as u64);
+    w.f64(game.transit);
+    w.f64(game.tick_rate);
+    w.f64(game.accumulator);
+    w.pose(game.previous_ship);
+    w.pose(game.previous_camera);
+
+    let camera = &game.camera;
+    w.vec3(camera.position);
+    w.quat(camera.rotation);
+    w.f64(camera.fov);
+    w.f64(camera.aspect);
+    w.mat4(camera.model);
+    w.mat4(camera.view);
+    for plane in camera.frustum {
+        w.vec4(plane);
+    }
+
+    write_ship(&mut w, &game.ship);
+
+    w.bool(game.target_lock.is_some());
+    w.u64(game.target_lock.unwrap_or(0) as u64);
+    let (tag, index) = match game.destination {
+        None => (0, 0),
+        Some(Destination::Planet) => (1, 0),
+        Some(Destination::Ring) => (2, 0),
+        Some(Destination::Bookmark(i)) => (3, i),
+        Some(Destination::System(i)) => (4, i),
+    };
+    w.u8(tag);
+    w.u64(index as u64);
+    w.u64(game.bookmarks.len() as u64);
+    for bookmark in &game.bookmarks {
+        w.u64(bookmark.system as u64);
+        w.vec3(bookmark.position);
+    }
+
+    w.u64(game.dust.len() as u64);
+    for dust in &game.dust {
+        w.vec3(dust.model.w_axis.truncate());
+        w.u32(dust.color);
+    }
+    w.u64(game.particles.len() as u64);
+    for particle in &game.particles {
+        w.vec3(particle.object.model.w_axis.truncate());
+        w.u32(particle.object.color);
+        w.vec3(particle.velocity);
+        w.f64(particle.lifetime);
+    }
+    w.u64(game.projectiles.len() as u64);
+    for projectile in &game.projectiles {
+        w.affine(projectile.object.model);
+        w.vec3(projectile.velocity);
+        w.f64(projectile.damage);
+        w.f64(projectile.lifetime);
+    }
+
+    w.u64(game.system_ticks);
+    let mut entries: Vec<(Option<usize>, usize, &Asteroid)> = Vec::new();
+    for asteroid in &game.asteroids {
+        match (entries.last_mut(), asteroid.generated) {
+            (Some((Some(start), len, _)), Some(index)) if *start + *len == index => *len += 1,
+            _ => entries.push((asteroid.generated, 1, asteroid)),
+        }
+    }
+    w.u64(game.asteroids.len() as u64);
+    w.u64(entries.len() as u64);
+    for (generated, len, asteroid) in entries {
+        w.bool(generated.is_some());
+        match generated {
+            Some(start) => {
+                w.u64(start as u64);
+                w.u64(len as u64);
+            }
+            None => write_asteroid(&mut w, asteroid),
+        }
+    }
+    w.u64(game.collision_grid.cursor as u64);
+    w.bytes
+}
+
+pub fn write_asteroid(w: &mut SaveWriter, asteroid: &Asteroid) {
+    w.affine(asteroid.object.model);
+    w.vec3(asteroid.rotation_axis);
+    w.f64(asteroid.rotation_speed);
+    w.f64(asteroid.integrity);
+    w.variant(asteroid.resource);
+    w.f64(asteroid.ore);
+    w.bool(asteroid.orbit.is_some());
+    if let Some(orbit) = asteroid.orbit {
+        w.f64(orbit.semi_major_axis);
+        w.f64(orbit.eccentricity);
+        w.vec3(orbit.periapsis);
+        w.vec3(orbit.normal);
+        w.f64(orbit.mean_motion);
+        w.f64(orbit.mean_anomaly);
+        w.f64(orbit.eccentric_anomaly);
+    }
+}
+
+pub fn write_ship(w: &mut SaveWriter, ship: &Ship) {
+    w.vec3(ship.position);
+    w.vec3(ship.velocity);
+    w.vec3(ship.acceleration);
+    w.quat(ship.rotation);
+    w.vec3(ship.angular_velocity);
+    w.vec3(ship.angular_acceleration);
+    ship.thrust.values().for_each(|&t| w.f64(t));
+    w.f64(ship.boost);
+    w.f64(ship.boost_cooldown);
+    w.bool(ship.jumping);
+    w.bool(ship.charging_jump);
+    w.f64(ship.jump_charge);
+    w.bool(ship.brake);
+    w.variant(ship.autopilot);
+    w.f64(ship.integrity);
+    w.f64(ship.shield);
+    w.f64(ship.shield_delay);
+    w.bool(ship.destroyed);
+    w.f64(ship.respawn_timer);
+    w.variant(ship.weapon);
+    w.bool(ship.firing);
+    w.f64(ship.weapon_cooldown);
+    w.bool(ship.laser.is_some());
+    w.vec3(ship.laser.unwrap_or(DVec3::ZERO));
+    ship.cargo.values().for_each(|&c| w.f64(c));
+    w.bool(ship.mining.is_some());
+    let (resource, ore) = ship.mining.unwrap_or((Resource::Ice, 0.0));
+    w.variant(resource);
+    w.f64(ore);
+    w.mat4(ship.hull.model);
+    write_ship_stats(w, &ship.stats);
+}
+
+pub fn write_ship_stats(w: &mut SaveWriter, stats: &ShipStats) {
+    w.f64(stats.thrust);
+    w.f64(stats.angular_thrust);
+    w.f64(stats.boost_strength);
+    w.f64(stats.boost_duration);
+    w.f64(stats.boost_cooldown);
+    w.f64(stats.jump_speed);
+    w.f64(stats.jump_charge);
+    w.mat3(stats.inertia);
+    w.f64(stats.collision_radius);
+    w.f64(stats.max_integrity);
+    w.f64(stats.max_shield);
+    w.f64(stats.shield_regen);
+    w.f64(stats.shield_delay);
+    w.f64(stats.integrity_regen);
+    w.f64(stats.g_tolerance);
+    w.f64(stats.g_damage);
+    w.f64(stats.respawn_time);
+    w.f64(stats.laser_range);
+    w.f64(stats.laser_dps);
+    w.f64(stats.cannon_speed);
+    w.f64(stats.cannon_damage);
+    w.f64(stats.cannon_rate);
+    w.f64(stats.cannon_lifetime);
+    w.f64(stats.mass);
+    w.f64(stats.cargo_capacity);
+    w.f64(stats.mining_range);
+    w.f64(stats.mining_rate);
+}
+
+// rebuilds the universe from the saved seed, then overwrites everything that changed since it was generated
+pub fn load_game(bytes: &[u8]) -> Result<Game> {
+    let mut r = SaveReader::new(bytes);
+    if r.take::<4>()? != *SAVE_MAGIC {
+        return Err(invalid_data("not a save file"));
+    }
+    if r.u32()? != SAVE_VERSION {
+        return Err(invalid_data("unsupported save version"));
+    }
+
+    let mut game = Game::with_seed(r.u64()?);
+    game.rng = ChaCha12Rng::from_seed(r.take()?);
+    game.rng.set_stream(r.u64()?);
+    game.rng.set_word_pos(r.u128()?);
+    game.system = r.index(game.galaxy.len())?;
+    if game.system != 0 {
+        let (stars, planet, asteroids) = generate_system(&game.galaxy[game.system]);
+        game.stars = stars;
+        game.planet = planet;
+        game.asteroids = asteroids;
+    }
+    game.transit = r.f64()?;
+    game.tick_rate = r.f64()?;
+    if !valid_tick_rate(game.tick_rate) {
+        return Err(invalid_data("corrupt tick rate"));
+    }
+    game.accumulator = r.f64()?;
+    game.previous_ship = r.pose()?;
+    game.previous_camera = r.pose()?;
+
+    let camera = &mut game.camera;
+    camera.position = r.vec3()?;
+    camera.rotation = r.quat()?;
+    camera.fov = r.f64()?;
+    camera.aspect = r.f64()?;
+    camera.model = r.mat4()?;
+    camera.view = r.mat4()?;
+    for plane in &mut camera.frustum {
+        *plane = r.vec4()?;
+    }
+
+    read_ship(&mut r, &mut game.ship)?;
+
+    let has_target = r.bool()?;
+    let target = r.u64()? as usize;
+    let tag = r.u8()?;
+    let index = r.u64()? as usize;
+    let bookmarks = r.u64()? as usize;
+    game.bookmarks.clear();
+    for _ in 0..bookmarks {
+        game.bookmarks.push(Bookmark { system: r.index(game.galaxy.len())?, position: r.vec3()? });
+    }
+    game.destination = match tag {
+        0 => None,
+        1 => Some(Destination::Planet),
+        2 => Some(Destination::Ring),
+        3 if index < game.bookmarks.len() => Some(Destination::Bookmark(index)),
+        4 if index < game.galaxy.len() => Some(Destination::System(index)),
+        _ => return Err(invalid_data("unknown destination")),
+    };
+
+    let count = r.u64()? as usize;
+    game.dust.clear();
+    for _ in 0..count {
+        let model = DMat4::from_translation(r.vec3()?);
+        game.dust.push(point_object(model, r.u32()?));
+    }
+    let count = r.u64()? as usize;
+    game.particles.clear();
+    for _ in 0..count {
+        let model = DMat4::from_translation(r.vec3()?);
+        game.particles.push(Particle {
+            object: point_object(model, r.u32()?),
+            velocity: r.vec3()?,
+            lifetime: r.f64()?,
+        });
+    }
+    let count = r.u64()? as usize;
+    game.projectiles.clear();
+    for _ in 0..count {
+        game.projectiles.push(Projectile {
+            object: projectile_object(r.affine()?),
+            velocity: r.vec3()?,
+            damage: r.f64()?,
+            lifetime: r.f64()?,
+        });
+    }
+
+    // untouched asteroids are taken from the regenerated system and moved ahead by the time spent in it,
+    // each at most once. in the list the planet keeps its own mesh, everything after it shares one asteroid mesh
+    game.system_ticks = r.u64()?;
+    let time = game.system_ticks as f64 / game.tick_rate;
+    let mut generated: Vec<Option<Asteroid>> = game.asteroids.drain(..).map(Some).collect();
+    let planet = generated[0].as_ref().unwrap().object.clone();
+    let rock = Object {
+        mesh: Rc::new(Mesh::new(parse_obj(ASTEROID_OBJ))),
+        model: DMat4::IDENTITY,
+        color: 0xffffffff,
+        fill: 0x000000ff,
+        lods: ASTEROID_LODS,
+    };
+    let count = r.u64()? as usize;
+    let entries = r.u64()?;
+    for _ in 0..entries {
+        if r.bool()? {
+            let start = r.u64()? as usize;
+            let len = r.u64()? as usize;
+            let run = generated.get_mut(start..start.saturating_add(len)).ok_or_else(|| invalid_data("corrupt asteroid run"))?;
+            for slot in run {
+                let mut asteroid = slot.take().ok_or_else(|| invalid_data("corrupt asteroid run"))?;
+                asteroid.object.model *= DMat4::from_axis_angle(asteroid.rotation_axis, asteroid.rotation_speed * time);
+                if let Some(orbit) = &mut asteroid.orbit {
+                    asteroid.object.model.w_axis = (game.planet.position + propagate_orbit(orbit, time)).extend(1.0);
+                }
+                game.asteroids.push(asteroid);
+            }
+        } else {
+            let template = if game.asteroids.is_empty() {&planet} else {&rock};
+            let asteroid = read_asteroid(&mut r, template)?;
+            game.asteroids.push(asteroid);
+        }
+        if game.asteroids.len() > count {
+            return Err(invalid_data("corrupt asteroid count"));
+        }
+    }
+    if game.asteroids.len() != count || count == 0 {
+        return Err(invalid_data("corrupt asteroid count"));
+    }
+    game.target_lock = if has_target && target < count {Some(target)} else {None};
+    update_collision_grid(&mut game.collision_grid, &game.asteroids);
+    game.collision_grid.cursor = r.u64()? as usize % count;
+    Ok(game)
+}
+
+pub fn read_asteroid(r: &mut SaveReader, template: &Object) -> Result<Asteroid> {
+    Ok(Asteroid {
+        generated: None,
+        object: Object { model: r.affine()?, ..template.clone() },
+        rotation_axis: r.vec3()?,
+        rotation_speed: r.f64()?,
+        integrity: r.f64()?,
+        resource: r.variant()?,
+        ore: r.f64()?,
+        orbit: if r.bool()? {Some(Orbit {
+            semi_major_axis: r.f64()?,
+            eccentricity: r.f64()?,
+            periapsis: r.vec3()?,
+            normal: r.vec3()?,
+            mean_motion: r.f64()?,
+            mean_anomaly: r.f64()?,
+            eccentric_anomaly: r.f64()?,
+        })} else {None},
+    })
+}
+
+pub fn read_ship(r: &mut SaveReader, ship: &mut Ship) -> Result<()> {
+    ship.position = r.vec3()?;
+    ship.velocity = r.vec3()?;
+    ship.acceleration = r.vec3()?;
+    ship.rotation = r.quat()?;
+    ship.angular_velocity = r.vec3()?;
+    ship.angular_acceleration = r.vec3()?;
+    for t in ship.thrust.values_mut() {
+        *t = r.f64()?;
+    }
+    ship.boost = r.f64()?;
+    ship.boost_cooldown = r.f64()?;
+    ship.jumping = r.bool()?;
+    ship.charging_jump = r.bool()?;
+    ship.jump_charge = r.f64()?;
+    ship.brake = r.bool()?;
+    ship.autopilot = r.variant()?;
+    ship.integrity = r.f64()?;
+    ship.shield = r.f64()?;
+    ship.shield_delay = r.f64()?;
+    ship.destroyed = r.bool()?;
+    ship.respawn_timer = r.f64()?;
+    ship.weapon = r.variant()?;
+    ship.firing = r.bool()?;
+    ship.weapon_cooldown = r.f64()?;
+    let has_laser = r.bool()?;
+    let laser = r.vec3()?;
+    ship.laser = has_laser.then_some(laser);
+    for c in ship.cargo.values_mut() {
+        *c = r.f64()?;
+    }
+    let is_mining = r.bool()?;
+    let mining = (r.variant()?, r.f64()?);
+    ship.mining = is_mining.then_some(mining);
+    ship.hull.model = r.mat4()?;
+    for thruster in ship.thrusters.values_mut() {
+        thruster.model = ship.hull.model;
+    }
+    read_ship_stats(r, &mut ship.stats)
+}
+
+pub fn read_ship_stats(r: &mut SaveReader, stats: &mut ShipStats) -> Result<()> {
+    stats.thrust = r.f64()?;
+    stats.angular_thrust = r.f64()?;
+    stats.boost_strength = r.f64()?;
+    stats.boost_duration = r.f64()?;
+    stats.boost_cooldown = r.f64()?;
+    stats.jump_speed = r.f64()?;
+    stats.jump_charge = r.f64()?;
+    stats.inertia = r.mat3()?;
+    stats.collision_radius = r.f64()?;
+    stats.max_integrity = r.f64()?;
+    stats.max_shield = r.f64()?;
+    stats.shield_regen = r.f64()?;
+    stats.shield_delay = r.f64()?;
+    stats.integrity_regen = r.f64()?;
+    stats.g_tolerance = r.f64()?;
+    stats.g_damage = r.f64()?;
+    stats.respawn_time = r.f64()?;
+    stats.laser_range = r.f64()?;
+    stats.laser_dps = r.f64()?;
+    stats.cannon_speed = r.f64()?;
+    stats.cannon_damage = r.f64()?;
+    stats.cannon_rate = r.f64()?;
+    stats.cannon_lifetime = r.f64()?;
+    stats.mass = r.f64()?;
+    stats.cargo_capacity = r.f64()?;
+    stats.mining_range = r.f64()?;
+    stats.mining_rate = r.f64()?;
+    Ok(())
+}
+
+// the "name = value" lines of a config file, blank lines and # comments are skipped and anything else is
+// warned about under the label
+pub fn config_entries<'a>(text: &'a str, label: &'a str) -> impl Iterator<Item = (&'a str, &'a str)> {
+    text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')).filter_map(move |line| {
+        let entry = line.split_once('=').map(|(name, value)| (name.trim(), value.trim()));
+        if entry.is_none() {
+            warn!("{label}: can't parse \"{line}\"");
+        }
+        entry
+    })
+}
+
+// a missing config file is created with the defaults so there is something to edit
+pub fn load_config<T>(name: &str, label: &str, parse: fn(&str) -> T, format: fn(&T) -> String, default: T) -> T {
+    match read_file(name) {
+        Ok(bytes) => parse(&String::from_utf8_lossy(&bytes)),
+        Err(_) => {
+            if let Err(err) = write_file(name, format(&default).as_bytes()) {
+                warn!("{label}: can't write {name}: {err}");
+            }
+            default
+        }
+    }
+}
+
+fn point_object(model: DMat4, color: u32) -> Object {
+    Object {
+        mesh: Rc::new(Mesh::new(vec![vec![DVec3::ZERO]])),
+        model,
+        color,
+        fill: 0x00000000,
+        lods: FULL_LODS,
+    }
+}
+
+// a file in the working directory on native, a local storage entry on wasm
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_file(name: &str, bytes: &[u8]) -> Result<()> {
+    std::fs::write(name, bytes)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_file(name: &str) -> Result<Vec<u8>> {
+    std::fs::read(name)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn write_file(name: &str, bytes: &[u8]) -> Result<()> {
+    local_storage()?.set_item(name, &encode_text(bytes)).map_err(|_| Error::other("local storage is full"))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn read_file(name: &str) -> Result<Vec<u8>> {
+    let text = local_storage()?.get_item(name).ok().flatten().ok_or(Error::new(ErrorKind::NotFound, "no such entry in local storage"))?;
+    decode_text(&text)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Result<web_sys::Storage> {
+    web_sys::window().and_then(|window| window.local_storage().ok().flatten()).ok_or(Error::other("local storage is unavailable"))
+}
+
+// local storage only holds strings and runs out at a few million characters, so 15 bits are packed into
+// each character, all of them below the surrogate range. the byte count goes in front to drop the padding
+#[cfg(target_arch = "wasm32")]
+fn encode_text(bytes: &[u8]) -> String {
+    let mut text = format!("{}:", bytes.len());
+    let (mut buffer, mut bits) = (0u32, 0);
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        if bits >= 15 {
+            bits -= 15;
+            text.push(char::from_u32((buffer >> bits) & 0x7fff).unwrap());
+        }
+    }
+    if bits > 0 {
+        text.push(char::from_u32((buffer << (15 - bits)) & 0x7fff).unwrap());
+    }
+    text
+}
+
+#[cfg(target_arch = "wasm32")]
+fn decode_text(text: &str) -> Result<Vec<u8>> {
+    let (len, data) = text.split_once(':').ok_or(invalid_data("not a save file"))?;
+    let len: usize = len.parse().map_err(|_| invalid_data("not a save file"))?;
+    let mut bytes = Vec::with_capacity(len);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in data.chars() {
+        buffer = (buffer << 15) | (c as u32 & 0x7fff);
+        bits += 15;
+        while bits >= 8 && bytes.len() < len {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+
+    // saving in the middle of a session, by hand or by autosave, mustn't change what happens next
+    #[test]
+    fn save_keeps_the_rng() {
+        let mut game = Game::with_seed(5);
+        for _ in 0..10 {
+            game.step(1.0 / 60.0);
+        }
+        let rng = game.rng.clone();
+        let mut loaded = load_game(&save_game(&game)).unwrap();
+        assert!(game.rng == rng);
+        assert!(loaded.rng == rng);
+        assert_eq!(game.rng.random::<u64>(), loaded.rng.random::<u64>());
+    }
+
+    // untouched asteroids come back from the seed at the same place, only the mined one takes up space
+    #[test]
+    fn save_stores_only_changed_asteroids() {
+        let mut game = Game::with_seed(5);
+        for _ in 0..600 {
+            game.update(1.0 / game.tick_rate);
+        }
+        let mined = &mut game.asteroids[3];
+        mined.ore /= 2.0;
+        mined.generated = None;
+        let bytes = save_game(&game);
+        // a full asteroid record alone is over 200 bytes, what is left is mostly the dust
+        assert!(bytes.len() < game.asteroids.len() * 8, "{} bytes for {} asteroids", bytes.len(), game.asteroids.len());
+
+        let loaded = load_game(&bytes).unwrap();
+        assert_eq!(loaded.asteroids.len(), game.asteroids.len());
+        for (a, b) in game.asteroids.iter().zip(&loaded.asteroids) {
+            assert!(a.object.model.abs_diff_eq(b.object.model, 1e-6 * a.object.model.w_axis.length()));
+            assert!(a.ore == b.ore && a.generated == b.generated);
+        }
+    }
+
+    #[test]
+    fn corrupt_tick_rate_is_rejected() {
+        let mut game = Game::with_seed(5);
+        game.tick_rate = 123.25;
+        let mut bytes = save_game(&game);
+        let offset = bytes.windows(8).position(|b| b == game.tick_rate.to_le_bytes()).unwrap();
+        for tick_rate in [0.0, -60.0, f64::NAN, f64::INFINITY] {
+            bytes[offset..offset + 8].copy_from_slice(&tick_rate.to_le_bytes());
+            assert!(load_game(&bytes).is_err());
+        }
+    }
+}
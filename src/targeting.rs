@@ -0,0 +1,86 @@
+use glam::{DVec2, DVec3};
+
+use crate::collision::*;
+use crate::game::*;
+use crate::graphics::*;
+use crate::orbits::*;
+use crate::sprites::*;
+use crate::transform::*;
+
+pub const TARGET_RANGE: f64 = 10000.0;
+pub const TARGET_COLOR: u32 = 0x00ff00ff;
+pub const WAYPOINT_COLOR: u32 = 0x4080ffff;
+
+pub struct TargetInfo {
+    pub name: String,
+    pub range: f64,
+    pub closing_speed: f64,
+}
+
+pub fn asteroid_velocity(asteroid: &Asteroid, planet: &Planet) -> DVec3 {
+    asteroid.orbit.map_or(DVec3::ZERO, |orbit| orbit_velocity(&orbit, planet.mu()))
+}
+
+// steps through the planet and asteroids in range ordered by distance, wrapping back to the nearest
+pub fn cycle_target(current: Option<usize>, position: DVec3, asteroids: &[Asteroid], grid: &SpatialHash) -> Option<usize> {
+    let mut candidates = Vec::new();
+    grid.query(position, TARGET_RANGE, &mut candidates);
+    let distance = |i: usize| (asteroids[i].object.model.transform_point3(DVec3::ZERO) - position).length();
+    candidates.retain(|&i| i == 0 || distance(i) <= TARGET_RANGE);
+    candidates.sort_by(|&a, &b| distance(a).total_cmp(&distance(b)));
+
+    let next = current.and_then(|c| candidates.iter().position(|&i| i == c)).map_or(0, |p| p + 1);
+    candidates.get(next).or(candidates.first()).copied()
+}
+
+pub fn target_info(index: usize, ship: &Ship, asteroids: &[Asteroid], planet: &Planet) -> TargetInfo {
+    let asteroid = &asteroids[index];
+    let offset = asteroid.object.model.transform_point3(DVec3::ZERO) - ship.position;
+    let relative_velocity = ship.velocity - asteroid_velocity(asteroid, planet);
+    TargetInfo {
+        name: if index == 0 {"PLANET".to_string()} else {format!("{} {:.0}t", asteroid.resource.name(), asteroid.ore)},
+        range: offset.length(),
+        closing_speed: relative_velocity.dot(offset.normalize_or_zero()),
+    }
+}
+
+// brackets around the target when it is on screen, otherwise an arrow at the edge pointing towards it
+pub fn draw_target_marker(target: &mut RenderTarget, center: DVec3, radius: f64, camera: &Camera, color: u32) {
+    let clip = transform_world_to_clip(center, camera);
+    let p = transform_clip_to_screen(clip, target);
+    let (w, h) = (target.width as f64, target.height as f64);
+
+    if clip.w > 0.0 && !out_of_bounds(p, 0, target) {
+        let size = f64::clamp(projected_size(center, radius, camera, target), 8.0, w) / 2.0 + 2.0;
+        let (p0, p1) = (DVec3::new(p.x - size, p.y - size, 0.0), DVec3::new(p.x + size, p.y + size, 0.0));
+        draw_rectangle(target, p0, p1, color);
+        return;
+    }
+
+    let view = camera.view.transform_point3(center);
+    let direction = DVec2::new(view.x, view.y).try_normalize().unwrap_or(DVec2::new(0.0, -1.0));
+    let margin = 8.0;
+    let extent = DVec2::new(w / 2.0 - margin, h / 2.0 - margin);
+    let scale = f64::min(extent.x / direction.x.abs(), extent.y / direction.y.abs());
+    let tip = DVec2::new(w / 2.0, h / 2.0) + direction * scale;
+    let back = tip - direction * 6.0;
+    let side = direction.perp() * 4.0;
+    let (tip, left, right) = (tip.extend(0.0), (back + side).extend(0.0), (back - side).extend(0.0));
+    draw_line(target, tip, left, color);
+    draw_line(target, tip, right, color);
+    draw_line(target, left, right, color);
+}
+
+pub fn draw_target_info(target: &mut RenderTarget, info: &TargetInfo) {
+    let time = if info.closing_speed > 0.0 {format!("{:.1} s", info.range / info.closing_speed)} else {"--".to_string()};
+    let lines = [
+        info.name.clone(),
+        format!("RNG {:.0} m", info.range),
+        format!("CLS {:.1} m/s", info.closing_speed),
+        format!("TTI {}", time),
+    ];
+    for (i, line) in lines.iter().enumerate() {
+        let y = 36.0 - 7.0 * i as f64;
+        draw_text(target, DVec3::new(target.width as f64 - (line.len() * 6) as f64, y, 0.0), line, FONT_5PX, 6, 1, TARGET_COLOR);
+    }
+}
@@ -1,6 +1,7 @@
 use core::f64;
+use std::mem::swap;
 use glam::{DVec3, DVec4, DMat4};
-use crate::{game::Camera, HEIGHT, WIDTH};
+use crate::{game::Camera, graphics::RenderTarget};
 
 pub const NEAR: f64 = 0.01;
 pub const FAR: f64 = 1000000.0;
@@ -17,41 +18,192 @@ pub fn transform_mesh(mesh: &Vec<Vec<DVec3>>, model: DMat4) -> Vec<Vec<DVec3>> {
 	mesh.iter().map(|p| transform_polygon(p, model)).collect()
 }
 
-pub fn transform_world_to_screen(vertex: DVec3, camera: &Camera) -> DVec3 {
-    let w = WIDTH as f64;
-    let h = HEIGHT as f64;
+pub fn projection_matrix(camera: &Camera) -> DMat4 {
     let n = NEAR;
     let f = FAR;
     let phi = camera.fov / 180.0 * f64::consts::PI;
     let r = f64::tan(phi/2.0) * n;
-    let t = r * h/w;
+    let t = r / camera.aspect;
 
-    let projection = DMat4::from_cols_array(&[
+    DMat4::from_cols_array(&[
         n/r, 0.0, 0.0, 0.0,
         0.0, n/t, 0.0, 0.0,
         0.0, 0.0, -(f+n)/(f-n), -2.0*f*n/(f-n),
         0.0, 0.0, -1.0, 0.0,
-    ]).transpose();
+    ]).transpose()
+}
 
+pub fn transform_world_to_clip(vertex: DVec3, camera: &Camera) -> DVec4 {
     let world = DVec4::new(vertex.x, vertex.y, vertex.z, 1.0);
     let eye = camera.view * world;
-    let clip = projection * eye;
+    projection_matrix(camera) * eye
+}
+
+pub fn transform_clip_to_screen(clip: DVec4, target: &RenderTarget) -> DVec3 {
+    let w = target.width as f64;
+    let h = target.height as f64;
+    let n = NEAR;
+    let f = FAR;
+
     let ndc = DVec3::new(clip.x/clip.w, clip.y/clip.w, clip.z/clip.w);
-    let screen = DVec3::new(
+    DVec3::new(
         w/2.0 * ndc.x + w/2.0, 
         h/2.0 * ndc.y + h/2.0, 
         (f-n)/2.0 * ndc.z + (f+n)/2.0
-    );
+    )
+}
+
+pub fn transform_world_to_screen(vertex: DVec3, camera: &Camera, target: &RenderTarget) -> DVec3 {
+    transform_clip_to_screen(transform_world_to_clip(vertex, camera), target)
+}
+
+// signed distances to the six frustum planes, positive inside (z in [-w, w])
+fn clip_distances(c: DVec4) -> [f64; 6] {
+    [
+        c.w + c.z, // near
+        c.w - c.z, // far
+        c.w + c.x, // left
+        c.w - c.x, // right
+        c.w + c.y, // bottom
+        c.w - c.y, // top
+    ]
+}
+
+pub fn inside_frustum(c: DVec4) -> bool {
+    clip_distances(c).iter().all(|d| *d >= 0.0)
+}
+
+pub fn clip_line(c0: DVec4, c1: DVec4) -> Option<(DVec4, DVec4)> {
+    let d0 = clip_distances(c0);
+    let d1 = clip_distances(c1);
+    let (mut t0, mut t1) = (0.0, 1.0);
+    for i in 0..6 {
+        if d0[i] < 0.0 && d1[i] < 0.0 {
+            return None;
+        }
+        if d0[i] < 0.0 {
+            t0 = f64::max(t0, d0[i] / (d0[i] - d1[i]));
+        } else if d1[i] < 0.0 {
+            t1 = f64::min(t1, d0[i] / (d0[i] - d1[i]));
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    Some((c0.lerp(c1, t0), c0.lerp(c1, t1)))
+}
+
+// faces with more vertices than this are drawn as a fan of triangles. clipping a convex polygon adds at most
+// one vertex per plane, concave or degenerate ones can gain more and are cut short at MAX_CLIPPED
+pub const MAX_POLYGON: usize = 16;
+pub const MAX_CLIPPED: usize = MAX_POLYGON + 6;
 
-    screen
+// fixed capacity vertex list so clipping doesn't allocate
+#[derive(Clone, Copy, Default)]
+pub struct ClippedPolygon {
+    pub vertices: [(DVec4, bool); MAX_CLIPPED],
+    pub len: usize,
 }
 
-pub fn out_of_bounds(p: DVec3, tolerance: i32) -> bool {
+impl ClippedPolygon {
+    // drops vertices past the capacity, which only distorts the outline of a polygon that was already odd
+    pub fn push(&mut self, vertex: (DVec4, bool)) {
+        if self.len == MAX_CLIPPED {
+            return;
+        }
+        self.vertices[self.len] = vertex;
+        self.len += 1;
+    }
+
+    pub fn as_slice(&self) -> &[(DVec4, bool)] {
+        &self.vertices[..self.len]
+    }
+}
+
+// sutherland-hodgman against each frustum plane, ping-ponging between two buffers. every vertex carries
+// a flag telling whether the edge to the next vertex belongs to the original polygon, so edges created
+// along the clip planes can be left out of the outline. at most MAX_POLYGON vertices
+pub fn clip_polygon(polygon: &[(DVec4, bool)]) -> ClippedPolygon {
+    let (mut front, mut back) = (ClippedPolygon::default(), ClippedPolygon::default());
+    let (mut input, mut output) = (&mut front, &mut back);
+    polygon.iter().for_each(|v| input.push(*v));
+    if polygon.iter().all(|(c, _)| inside_frustum(*c)) {
+        return *input;
+    }
+    for i in 0..6 {
+        if input.as_slice().iter().all(|(c, _)| clip_distances(*c)[i] >= 0.0) {
+            continue;
+        }
+        output.len = 0;
+        for j in 0..input.len {
+            let (a, edge) = input.vertices[j];
+            let (b, _) = input.vertices[(j+1) % input.len];
+            let da = clip_distances(a)[i];
+            let db = clip_distances(b)[i];
+            if da >= 0.0 {
+                output.push((a, edge));
+                if db < 0.0 {
+                    output.push((a.lerp(b, da / (da - db)), false));
+                }
+            } else if db >= 0.0 {
+                output.push((a.lerp(b, da / (da - db)), edge));
+            }
+        }
+        swap(&mut input, &mut output);
+        if input.len == 0 {
+            break;
+        }
+    }
+    *input
+}
+
+// approximate diameter in pixels of a sphere on screen
+pub fn projected_size(center: DVec3, radius: f64, camera: &Camera, target: &RenderTarget) -> f64 {
+    let distance = (center - camera.position).length();
+    if distance <= radius {
+        return f64::INFINITY;
+    }
+    let phi = camera.fov / 180.0 * f64::consts::PI;
+    radius / (distance * f64::tan(phi/2.0)) * target.width as f64
+}
+
+// planes of the view frustum in world space as (normal, distance), normals pointing inwards
+pub fn frustum_planes(camera: &Camera) -> [DVec4; 6] {
+    let m = projection_matrix(camera) * camera.view;
+    let (r0, r1, r2, r3) = (m.row(0), m.row(1), m.row(2), m.row(3));
+    [r3 + r2, r3 - r2, r3 + r0, r3 - r0, r3 + r1, r3 - r1].map(|p| p / p.truncate().length())
+}
+
+pub fn sphere_in_frustum(planes: &[DVec4; 6], center: DVec3, radius: f64) -> bool {
+    planes.iter().all(|p| p.truncate().dot(center) + p.w >= -radius)
+}
+
+pub fn out_of_bounds(p: DVec3, tolerance: i32, target: &RenderTarget) -> bool {
     (p.x as i32) < 0 - tolerance || 
-    (p.x as i32) >= WIDTH as i32 + tolerance || 
+    (p.x as i32) >= target.width as i32 + tolerance || 
     (p.y as i32) < 0 - tolerance || 
-    (p.y as i32) >= HEIGHT as i32 + tolerance ||
+    (p.y as i32) >= target.height as i32 + tolerance ||
     // p.z < NEAR ||
     p.z < 0.0 ||
     p.z > FAR
 }
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::TAU;
+
+    use super::*;
+
+    // a star zig-zags across every frustum plane, so each pass adds far more than one vertex
+    #[test]
+    fn clipping_concave_polygon_stays_in_capacity() {
+        let star: Vec<(DVec4, bool)> = (0..MAX_POLYGON).map(|i| {
+            let angle = i as f64 / MAX_POLYGON as f64 * TAU;
+            let r = if i % 2 == 0 {3.0} else {0.5};
+            (DVec4::new(r * angle.cos(), r * angle.sin(), r * angle.sin() * 0.9, 1.0), true)
+        }).collect();
+        let clipped = clip_polygon(&star);
+        assert!(clipped.len > 0 && clipped.len <= MAX_CLIPPED);
+        assert!(clipped.as_slice().iter().all(|(c, _)| clip_distances(*c).iter().all(|d| *d >= -1e-9)));
+    }
+}
@@ -0,0 +1,236 @@
+use std::rc::Rc;
+
+use enum_map::Enum;
+use glam::{DMat4, DQuat, DVec3};
+use rand::Rng;
+use rand_distr::StandardNormal;
+
+use crate::collision::*;
+use crate::game::*;
+use crate::graphics::object_bounds;
+use crate::meshes::*;
+use crate::mining::*;
+use crate::orbits::*;
+
+// asteroid integrity per unit of scale, pieces smaller than the minimum scale are destroyed outright.
+// the pieces of a broken asteroid share its volume
+pub const ASTEROID_INTEGRITY: f64 = 5.0;
+pub const ASTEROID_SPLIT_COUNT: usize = 3;
+pub const ASTEROID_MIN_SCALE: f64 = 1.0;
+pub const ASTEROID_SPLIT_SPEED: f64 = 2.0;
+
+// the bow of the hull mesh
+pub const MUZZLE_OFFSET: DVec3 = DVec3::new(0.0, 0.0, -2.5);
+
+#[derive(Enum, Clone, Copy, PartialEq)]
+pub enum Weapon {
+    Laser,
+    Cannon,
+    Miner,
+}
+
+pub struct Projectile {
+    pub object: Object,
+    pub velocity: DVec3,
+    pub damage: f64,
+    pub lifetime: f64,
+}
+
+impl Weapon {
+    pub fn name(self) -> &'static str {
+        match self {
+            Weapon::Laser => "LASER",
+            Weapon::Cannon => "CANNON",
+            Weapon::Miner => "MINER",
+        }
+    }
+
+    pub fn next(self) -> Weapon {
+        match self {
+            Weapon::Laser => Weapon::Cannon,
+            Weapon::Cannon => Weapon::Miner,
+            Weapon::Miner => Weapon::Laser,
+        }
+    }
+}
+
+// the laser hits instantly and deals damage over time, the cannon spawns projectiles at a fixed rate
+pub fn update_weapons(ship: &mut Ship, projectiles: &mut Vec<Projectile>, asteroids: &mut [Asteroid], grid: &SpatialHash, dt: f64) {
+    ship.laser = None;
+    ship.mining = None;
+    ship.weapon_cooldown = f64::max(0.0, ship.weapon_cooldown - dt);
+    if !ship.firing || ship.jumping || ship.destroyed {
+        return;
+    }
+
+    let muzzle = ship.position + ship.rotation * MUZZLE_OFFSET;
+    let forward = ship.rotation * DVec3::new(0.0, 0.0, -1.0);
+    match ship.weapon {
+        Weapon::Laser => {
+            let range = ship.stats.laser_range;
+            let hit = raycast_asteroids(muzzle, forward, range, asteroids, grid);
+            if let Some((i, _)) = hit {
+                asteroids[i].integrity -= ship.stats.laser_dps * dt;
+                asteroids[i].generated = None;
+            }
+            ship.laser = Some(muzzle + forward * hit.map_or(range, |(_, t)| t));
+        }
+        Weapon::Cannon => {
+            if ship.weapon_cooldown == 0.0 {
+                ship.weapon_cooldown = 1.0 / ship.stats.cannon_rate;
+                projectiles.push(Projectile {
+                    object: projectile_object(DMat4::from_rotation_translation(ship.rotation, muzzle)),
+                    velocity: ship.velocity + forward * ship.stats.cannon_speed,
+                    damage: ship.stats.cannon_damage,
+                    lifetime: ship.stats.cannon_lifetime,
+                });
+            }
+        }
+        Weapon::Miner => {
+            ship.laser = Some(update_mining_beam(ship, asteroids, grid, muzzle, forward, dt));
+        }
+    }
+}
+
+pub fn projectile_object(model: DMat4) -> Object {
+    Object {
+        mesh: Rc::new(Mesh::new(projectile_mesh())),
+        model,
+        color: 0xff00ffff,
+        fill: 0x00000000,
+        lods: FULL_LODS,
+    }
+}
+
+// projectiles are swept along their path each tick so fast ones can't tunnel through small asteroids
+pub fn update_projectiles(projectiles: &mut Vec<Projectile>, asteroids: &mut [Asteroid], grid: &SpatialHash, dt: f64) {
+    for projectile in projectiles.iter_mut() {
+        let position = projectile.object.model.transform_point3(DVec3::ZERO);
+        let step = projectile.velocity * dt;
+        let hit = step.try_normalize().and_then(|direction| raycast_asteroids(position, direction, step.length(), asteroids, grid));
+        if let Some((i, _)) = hit {
+            asteroids[i].integrity -= projectile.damage;
+            asteroids[i].generated = None;
+            projectile.lifetime = 0.0;
+        }
+        projectile.object.model = DMat4::from_translation(step) * projectile.object.model;
+        projectile.lifetime -= dt;
+    }
+    projectiles.retain(|p| p.lifetime > 0.0);
+}
+
+// replaces destroyed asteroids with smaller pieces, the grid is rebuilt and the target lock fixed up since indices shift
+pub fn break_asteroids(asteroids: &mut Vec<Asteroid>, planet: &Planet, grid: &mut SpatialHash, target_lock: &mut Option<usize>, particles: &mut Vec<Particle>, rng: &mut impl Rng) {
+    if asteroids.iter().all(|a| a.integrity > 0.0) {
+        return;
+    }
+
+    let mut i = 0;
+    while i < asteroids.len() {
+        if asteroids[i].integrity > 0.0 {
+            i += 1;
+            continue;
+        }
+        let last = asteroids.len() - 1;
+        let asteroid = asteroids.swap_remove(i);
+        if *target_lock == Some(i) {
+            *target_lock = None;
+        } else if *target_lock == Some(last) {
+            *target_lock = Some(i);
+        }
+        let center = asteroid.object.model.transform_point3(DVec3::ZERO);
+        let (_, radius) = object_bounds(&asteroid.object);
+        let velocity = asteroid.orbit.map_or(DVec3::ZERO, |orbit| orbit_velocity(&orbit, planet.mu()));
+        add_explosion_particles(particles, center, velocity, radius, rng);
+
+        let scale = asteroid.object.model.x_axis.truncate().length() / (ASTEROID_SPLIT_COUNT as f64).cbrt();
+        if scale < ASTEROID_MIN_SCALE {
+            continue;
+        }
+        for _ in 0..ASTEROID_SPLIT_COUNT {
+            let direction = DVec3::new(
+                rng.sample::<f64, StandardNormal>(StandardNormal),
+                rng.sample::<f64, StandardNormal>(StandardNormal),
+                rng.sample::<f64, StandardNormal>(StandardNormal),
+            ).normalize();
+            let rotation = DQuat::from_axis_angle(direction.any_orthonormal_vector(), rng.random::<f64>() * 2.0 * std::f64::consts::PI);
+            let position = center + direction * radius * 0.5;
+            asteroids.push(Asteroid {
+                generated: None,
+                object: Object {
+                    model: DMat4::from_scale_rotation_translation(DVec3::ONE * scale, rotation, position),
+                    ..asteroid.object.clone()
+                },
+                rotation_axis: direction,
+                rotation_speed: rng.random_range(-1.0..1.0),
+                integrity: scale * ASTEROID_INTEGRITY,
+                resource: asteroid.resource,
+                ore: asteroid.ore / ASTEROID_SPLIT_COUNT as f64,
+                orbit: asteroid.orbit.and_then(|_| orbit_from_state(planet.mu(), position - planet.position, velocity + direction * ASTEROID_SPLIT_SPEED)),
+            });
+        }
+    }
+    update_collision_grid(grid, asteroids);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::collision::tests::{orbiting_rock, outlying_point};
+
+    use super::*;
+
+    fn scale(asteroid: &Asteroid) -> f64 {
+        asteroid.object.model.x_axis.truncate().length()
+    }
+
+    // the lock goes with the broken rock, the pieces keep its volume and ore between them
+    #[test]
+    fn breaking_locked_asteroid_clears_lock() {
+        let (mut game, i) = orbiting_rock();
+        let (parent, ore, count) = (scale(&game.asteroids[i]), game.asteroids[i].ore, game.asteroids.len());
+        assert!(parent / (ASTEROID_SPLIT_COUNT as f64).cbrt() >= ASTEROID_MIN_SCALE);
+        game.asteroids[i].integrity = 0.0;
+        game.target_lock = Some(i);
+
+        break_asteroids(&mut game.asteroids, &game.planet, &mut game.collision_grid, &mut game.target_lock, &mut game.particles, &mut game.rng);
+        assert_eq!(game.target_lock, None);
+        assert_eq!(game.asteroids.len(), count - 1 + ASTEROID_SPLIT_COUNT);
+        let pieces = &game.asteroids[count - 1..];
+        let volume: f64 = pieces.iter().map(|a| scale(a).powi(3)).sum();
+        assert!((volume - parent.powi(3)).abs() < 1e-9 * volume);
+        assert!((pieces.iter().map(|a| a.ore).sum::<f64>() - ore).abs() < 1e-9 * ore);
+        assert!(pieces.iter().all(|a| a.integrity > 0.0 && a.orbit.is_some() && a.generated.is_none()));
+    }
+
+    // the last asteroid is swapped into the broken one's slot, a lock on it has to follow
+    #[test]
+    fn breaking_other_asteroid_remaps_lock() {
+        let (mut game, i) = orbiting_rock();
+        let last = game.asteroids.len() - 1;
+        let locked = game.asteroids[last].object.model;
+        game.asteroids[i].integrity = 0.0;
+        game.target_lock = Some(last);
+
+        break_asteroids(&mut game.asteroids, &game.planet, &mut game.collision_grid, &mut game.target_lock, &mut game.particles, &mut game.rng);
+        assert_eq!(game.target_lock, Some(i));
+        assert!(game.asteroids[i].object.model == locked);
+    }
+
+    #[test]
+    fn projectile_hits_asteroid() {
+        let (mut game, i) = orbiting_rock();
+        let (point, outward) = outlying_point(&game.asteroids[i].object);
+        let integrity = game.asteroids[i].integrity;
+        game.projectiles.push(Projectile {
+            object: projectile_object(DMat4::from_translation(point + outward * 10.0)),
+            velocity: -outward * 1000.0,
+            damage: 1.0,
+            lifetime: 1.0,
+        });
+
+        update_projectiles(&mut game.projectiles, &mut game.asteroids, &game.collision_grid, 1.0 / 60.0);
+        assert!(game.projectiles.is_empty());
+        assert_eq!(game.asteroids[i].integrity, integrity - 1.0);
+        assert!(game.asteroids[i].generated.is_none());
+    }
+}